@@ -132,8 +132,8 @@ fn server_update_system(
                 let message = bincode::serialize(&ServerMessages::PlayerConnected { id: *id }).unwrap();
                 server.broadcast_message(DefaultChannel::Reliable, message);
             }
-            ServerEvent::ClientDisconnected(id) => {
-                println!("Player {} disconnected.", id);
+            ServerEvent::ClientDisconnected(id, reason) => {
+                println!("Player {} disconnected: {}", id, reason);
                 if let Some(player_entity) = lobby.players.remove(id) {
                     commands.entity(player_entity).despawn();
                 }
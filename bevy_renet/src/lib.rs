@@ -100,7 +100,7 @@ impl RenetServerPlugin {
 
     pub fn send_packets_system(mut server: ResMut<RenetServer>, mut renet_error: EventWriter<RenetError>) {
         if let Err(e) = server.send_packets() {
-            renet_error.send(RenetError::IO(e));
+            renet_error.send(e);
         }
     }
 
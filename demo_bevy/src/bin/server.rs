@@ -121,8 +121,8 @@ fn server_update_system(
                 .unwrap();
                 server.broadcast_message(ServerChannel::ServerMessages, message);
             }
-            ServerEvent::ClientDisconnected(id) => {
-                println!("Player {} disconnected.", id);
+            ServerEvent::ClientDisconnected(id, reason) => {
+                println!("Player {} disconnected: {}", id, reason);
                 visualizer.remove_client(*id);
                 if let Some(player_entity) = lobby.players.remove(id) {
                     commands.entity(player_entity).despawn();
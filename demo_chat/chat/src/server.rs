@@ -85,7 +85,7 @@ impl ChatServer {
                     let init_message = bincode::options().serialize(&init_message).unwrap();
                     self.server.send_message(client_id, DefaultChannel::Reliable, init_message);
                 }
-                ServerEvent::ClientDisconnected(client_id) => {
+                ServerEvent::ClientDisconnected(client_id, _) => {
                     self.visualizer.remove_client(client_id);
                     self.usernames.remove(&client_id);
                     let message = bincode::options()
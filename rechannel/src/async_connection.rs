@@ -0,0 +1,228 @@
+use crate::error::RechannelError;
+use crate::packet::Payload;
+use crate::remote_connection::{ConnectionConfig, RemoteConnection};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+
+use std::fmt;
+use std::future::poll_fn;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+// Large enough to receive any UDP datagram.
+const RECEIVE_BUFFER_SIZE: usize = 65536;
+
+/// Possible errors from an [AsyncRemoteConnection].
+#[derive(Debug)]
+pub enum AsyncConnectionError {
+    /// An error occurred in the socket
+    IoError(std::io::Error),
+    /// An error occurred in the connection
+    RechannelError(RechannelError),
+}
+
+impl std::error::Error for AsyncConnectionError {}
+
+impl fmt::Display for AsyncConnectionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use AsyncConnectionError::*;
+
+        match *self {
+            IoError(ref io_err) => write!(fmt, "{}", io_err),
+            RechannelError(ref rechannel_err) => write!(fmt, "{}", rechannel_err),
+        }
+    }
+}
+
+impl From<std::io::Error> for AsyncConnectionError {
+    fn from(inner: std::io::Error) -> Self {
+        AsyncConnectionError::IoError(inner)
+    }
+}
+
+impl From<RechannelError> for AsyncConnectionError {
+    fn from(inner: RechannelError) -> Self {
+        AsyncConnectionError::RechannelError(inner)
+    }
+}
+
+/// A [RemoteConnection] that sends and receives its packets with a tokio [UdpSocket].
+/// Packets received from addresses other than the remote address are ignored.
+#[derive(Debug)]
+pub struct AsyncRemoteConnection {
+    socket: UdpSocket,
+    remote_addr: SocketAddr,
+    connection: RemoteConnection,
+    buffer: Box<[u8]>,
+}
+
+impl AsyncRemoteConnection {
+    pub fn new(current_time: Duration, config: ConnectionConfig, socket: UdpSocket, remote_addr: SocketAddr) -> Self {
+        Self {
+            socket,
+            remote_addr,
+            connection: RemoteConnection::new(current_time, config),
+            buffer: vec![0u8; RECEIVE_BUFFER_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn connection(&self) -> &RemoteConnection {
+        &self.connection
+    }
+
+    pub fn connection_mut(&mut self) -> &mut RemoteConnection {
+        &mut self.connection
+    }
+
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    /// Changes the remote address, for when the remote changed networks
+    /// and its packets arrive from a new address (NAT rebinding).
+    pub fn rebind(&mut self, new_addr: SocketAddr) {
+        log::debug!("Remote address changed from {} to {}", self.remote_addr, new_addr);
+        self.remote_addr = new_addr;
+    }
+
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.connection.advance_time(duration);
+    }
+
+    pub fn update(&mut self) -> Result<(), RechannelError> {
+        self.connection.update()
+    }
+
+    /// Send a message over a channel, returns [RechannelError::ChannelMaxMessagesLimit]
+    /// when the channel cannot queue more messages, the message should be sent again later.
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), RechannelError> {
+        let channel_id = channel_id.into();
+        if !self.connection.send_channels_id().contains(&channel_id) {
+            return Err(RechannelError::InvalidChannelId(channel_id));
+        }
+        if !self.connection.can_send_message(channel_id) {
+            return Err(RechannelError::ChannelMaxMessagesLimit);
+        }
+
+        self.connection.try_send_message(channel_id, message)
+    }
+
+    pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Payload> {
+        self.connection.receive_message(channel_id)
+    }
+
+    /// Sends the packets generated by the connection to the remote address.
+    pub async fn send_packets(&mut self) -> Result<(), AsyncConnectionError> {
+        let packets = self.connection.get_packets_to_send()?;
+        for packet in packets.iter() {
+            self.socket.send_to(packet, self.remote_addr).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for a packet from the remote address and processes it.
+    pub async fn recv(&mut self) -> Result<(), AsyncConnectionError> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Returns the next message received in the channel,
+    /// waiting for packets from the remote address if there is none.
+    pub async fn recv_message<I: Into<u8>>(&mut self, channel_id: I) -> Result<Payload, AsyncConnectionError> {
+        let channel_id = channel_id.into();
+        loop {
+            if let Some(message) = self.connection.receive_message(channel_id) {
+                return Ok(message);
+            }
+            self.recv().await?;
+        }
+    }
+
+    /// Returns a stream of the messages received in the channel.
+    /// The stream ends when the connection is disconnected.
+    pub fn messages<I: Into<u8>>(&mut self, channel_id: I) -> MessageStream<'_> {
+        MessageStream {
+            connection: self,
+            channel_id: channel_id.into(),
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), AsyncConnectionError>> {
+        loop {
+            let mut buffer = ReadBuf::new(&mut self.buffer);
+            let addr = match self.socket.poll_recv_from(cx, &mut buffer) {
+                Poll::Ready(Ok(addr)) => addr,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if addr != self.remote_addr {
+                log::debug!("Ignored packet from unknown address {}", addr);
+                continue;
+            }
+
+            let packet = buffer.filled();
+            return Poll::Ready(self.connection.process_packet(packet).map(|_| ()).map_err(Into::into));
+        }
+    }
+}
+
+/// Stream of the messages received in a channel of an [AsyncRemoteConnection].
+#[derive(Debug)]
+pub struct MessageStream<'a> {
+    connection: &'a mut AsyncRemoteConnection,
+    channel_id: u8,
+}
+
+impl<'a> Stream for MessageStream<'a> {
+    type Item = Result<Payload, AsyncConnectionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.connection.connection.disconnected().is_some() {
+                return Poll::Ready(None);
+            }
+
+            if let Some(message) = this.connection.connection.receive_message(this.channel_id) {
+                return Poll::Ready(Some(Ok(message)));
+            }
+
+            match this.connection.poll_recv(cx) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_and_receive_messages() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let remote_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let addr = socket.local_addr().unwrap();
+            let remote_addr = remote_socket.local_addr().unwrap();
+
+            let mut connection = AsyncRemoteConnection::new(Duration::ZERO, ConnectionConfig::default(), socket, remote_addr);
+            let mut remote_connection = AsyncRemoteConnection::new(Duration::ZERO, ConnectionConfig::default(), remote_socket, addr);
+
+            connection.send_message(0, vec![1, 2, 3]).unwrap();
+            connection.send_packets().await.unwrap();
+
+            let message = remote_connection.recv_message(0).await.unwrap();
+            assert_eq!(message, vec![1, 2, 3]);
+        });
+    }
+}
@@ -51,6 +51,18 @@ pub struct ChunkChannelConfig {
     pub max_message_size: u64,
     /// Queue size for the chunk channel.
     pub message_send_queue_size: usize,
+    /// When set, messages are not accepted when the bytes of the messages waiting
+    /// to be sent, including the chunk being sent, are above this limit.
+    pub max_queued_bytes: Option<usize>,
+}
+
+/// Progress of the chunk message being sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkProgress {
+    /// Number of bytes acked by the receiver.
+    pub bytes_sent: usize,
+    /// Total number of bytes of the message.
+    pub bytes_total: usize,
 }
 
 #[derive(Debug)]
@@ -76,6 +88,7 @@ pub struct SendChunkChannel {
     packet_budget: u64,
     max_message_size: u64,
     message_send_queue_size: usize,
+    max_queued_bytes: Option<usize>,
     packets_sent: SequenceBuffer<PacketSent>,
     messages_to_send: VecDeque<Bytes>,
     error: Option<ChannelError>,
@@ -114,6 +127,7 @@ impl Default for ChunkChannelConfig {
             packet_budget: 8 * 1024,
             max_message_size: 256 * 1024,
             message_send_queue_size: 8,
+            max_queued_bytes: None,
         }
     }
 }
@@ -140,6 +154,7 @@ impl SendChunkChannel {
             resend_time: config.resend_time,
             channel_id: config.channel_id,
             message_send_queue_size: config.message_send_queue_size,
+            max_queued_bytes: config.max_queued_bytes,
             sending: Sending::No,
             packets_sent: SequenceBuffer::with_capacity(config.sent_packet_buffer_size),
             messages_to_send: VecDeque::with_capacity(config.message_send_queue_size),
@@ -344,6 +359,64 @@ impl SendChannel for SendChunkChannel {
         self.messages_to_send.len() < self.message_send_queue_size
     }
 
+    fn has_messages_ready(&self, current_time: Duration) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+
+        match &self.sending {
+            Sending::No => !self.messages_to_send.is_empty(),
+            Sending::Yes { acked, resend_timers, .. } => acked
+                .iter()
+                .zip(resend_timers.iter())
+                .any(|(acked, timer)| !acked && timer.is_finished(current_time)),
+        }
+    }
+
+    fn pending_messages(&self) -> usize {
+        let sending = usize::from(matches!(self.sending, Sending::Yes { .. }));
+        self.messages_to_send.len() + sending
+    }
+
+    fn pending_bytes(&self) -> usize {
+        let sending_bytes = match self.chunk_progress() {
+            Some(progress) => progress.bytes_total - progress.bytes_sent,
+            None => 0,
+        };
+        self.messages_to_send.iter().map(|message| message.len()).sum::<usize>() + sending_bytes
+    }
+
+    fn max_queued_bytes(&self) -> Option<usize> {
+        self.max_queued_bytes
+    }
+
+    fn max_message_size(&self) -> u64 {
+        self.max_message_size
+    }
+
+    fn chunk_progress(&self) -> Option<ChunkProgress> {
+        match &self.sending {
+            Sending::No => None,
+            Sending::Yes { acked, data, .. } => {
+                let bytes_sent = acked
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &acked)| acked)
+                    .map(|(slice_id, _)| {
+                        let start = slice_id * self.slice_size;
+                        let end = data.len().min(start + self.slice_size);
+                        end - start
+                    })
+                    .sum();
+
+                Some(ChunkProgress {
+                    bytes_sent,
+                    bytes_total: data.len(),
+                })
+            }
+        }
+    }
+
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
@@ -497,7 +570,10 @@ impl ReceiveChannel for ReceiveChunkChannel {
         }
 
         for message in messages.iter() {
-            match bincode::options().deserialize::<SliceMessage>(message) {
+            match bincode::options()
+                .with_limit(message.len() as u64)
+                .deserialize::<SliceMessage>(message)
+            {
                 Ok(slice_message) => match self.process_slice_message(&slice_message) {
                     Ok(Some(message)) => self.messages_received.push_back(message),
                     Ok(None) => {}
@@ -583,6 +659,31 @@ mod tests {
         assert_eq!(payload, received_payload);
     }
 
+    #[test]
+    fn chunk_progress() {
+        let current_time = Duration::ZERO;
+        let config = ChunkChannelConfig {
+            slice_size: 10,
+            packet_budget: 30,
+            ..Default::default()
+        };
+        let mut send_channel = SendChunkChannel::new(config);
+        assert!(send_channel.chunk_progress().is_none());
+
+        send_channel.send_message(Bytes::from(vec![255u8; 35]), Duration::ZERO);
+        let progress = send_channel.chunk_progress().unwrap();
+        assert_eq!(progress.bytes_sent, 0);
+        assert_eq!(progress.bytes_total, 35);
+
+        send_channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+        send_channel.process_ack(0);
+        assert_eq!(send_channel.chunk_progress().unwrap().bytes_sent, 20);
+
+        send_channel.get_messages_to_send(u64::MAX, 1, current_time).unwrap();
+        send_channel.process_ack(1);
+        assert!(send_channel.chunk_progress().is_none());
+    }
+
     #[test]
     fn chunk_channel_queue() {
         let current_time = Duration::ZERO;
@@ -4,7 +4,7 @@ pub(crate) mod unreliable;
 
 use std::time::Duration;
 
-pub use block::ChunkChannelConfig;
+pub use block::{ChunkChannelConfig, ChunkProgress};
 pub use reliable::ReliableChannelConfig;
 pub use unreliable::UnreliableChannelConfig;
 
@@ -31,8 +31,46 @@ pub enum ChannelConfig {
 pub(crate) trait SendChannel: std::fmt::Debug {
     fn get_messages_to_send(&mut self, available_bytes: u64, sequence: u16, current_time: Duration) -> Option<ChannelPacketData>;
     fn send_message(&mut self, payload: Bytes, current_time: Duration);
+    /// Send a message with a priority, only channels that can choose which messages
+    /// to send first use the priority, the others send it as a normal message.
+    fn send_message_with_priority(&mut self, payload: Bytes, _priority: u8, current_time: Duration) {
+        self.send_message(payload, current_time)
+    }
     fn process_ack(&mut self, ack: u16);
+    /// Notifies that the packet was lost, only channels that resend messages use it.
+    fn process_nack(&mut self, _nack: u16) {}
     fn can_send_message(&self) -> bool;
+    /// Returns true if the channel has messages ready to be sent in the next packet.
+    fn has_messages_ready(&self, current_time: Duration) -> bool;
+    /// Returns the number of messages waiting to be sent or acked.
+    fn pending_messages(&self) -> usize;
+    /// Returns the number of bytes of the messages waiting to be sent or acked.
+    fn pending_bytes(&self) -> usize;
+    /// Returns the limit of pending bytes, messages above it are not accepted.
+    fn max_queued_bytes(&self) -> Option<usize>;
+    fn max_message_size(&self) -> u64;
+    /// Returns false if the channel uses empty messages internally, so they cannot be sent.
+    fn accepts_empty_messages(&self) -> bool {
+        true
+    }
+    /// Updates the retransmission timeout calculated from the connection RTT.
+    fn set_retransmission_timeout(&mut self, _timeout: Duration) {}
+    /// Returns the progress of the chunk message being sent, only used by chunk channels.
+    fn chunk_progress(&self) -> Option<ChunkProgress> {
+        None
+    }
+    /// Returns the id that will be used by the next message sent, only used by channels that track delivery.
+    fn next_message_id(&self) -> Option<u16> {
+        None
+    }
+    /// Returns the ids of the messages acked since the last call, only used by channels that track delivery.
+    fn take_delivered_messages(&mut self) -> Vec<u16> {
+        vec![]
+    }
+    /// Returns the ids of the messages that expired since the last call, only used by channels with a message TTL.
+    fn take_expired_messages(&mut self) -> Vec<u16> {
+        vec![]
+    }
     fn error(&self) -> Option<ChannelError>;
 }
 
@@ -93,6 +131,19 @@ impl ChannelConfig {
         }
     }
 
+    /// Returns the size of the sequence buffers used by the channel.
+    pub(crate) fn sequence_buffers_size(&self) -> Vec<(&'static str, usize)> {
+        match self {
+            ChannelConfig::Unreliable(_) => vec![],
+            ChannelConfig::Reliable(config) => vec![
+                ("reliable channel sent packet buffer", config.sent_packet_buffer_size),
+                ("reliable channel message send queue", config.message_send_queue_size),
+                ("reliable channel message receive queue", config.message_receive_queue_size),
+            ],
+            ChannelConfig::Chunk(config) => vec![("chunk channel sent packet buffer", config.sent_packet_buffer_size)],
+        }
+    }
+
     pub fn channel_id(&self) -> u8 {
         match self {
             ChannelConfig::Unreliable(config) => config.channel_id,
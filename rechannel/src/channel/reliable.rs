@@ -22,6 +22,11 @@ struct ReliableMessage {
 pub struct ReliableMessageSent {
     reliable_message: ReliableMessage,
     resend_timer: Timer,
+    num_sends: u32,
+    priority: u8,
+    send_time: Duration,
+    // Sequence of the last packet that carried the message
+    last_sequence: u16,
 }
 
 #[derive(Debug, Clone)]
@@ -51,8 +56,27 @@ pub struct ReliableChannelConfig {
     pub max_message_size: u64,
     /// Delay to wait before resending messages
     pub message_resend_time: Duration,
+    /// If set to true, the delay to wait before resending messages is the retransmission timeout
+    /// calculated from the connection RTT and its variance. The message resend time is used until
+    /// the RTT is measured, and as the minimum delay so messages are not resent before they can be acked.
+    pub adaptive_resend_time: bool,
+    /// Maximum number of times a message can be resent, if a message is not acked after
+    /// that the channel errors and the connection is disconnected. No limit when None.
+    pub max_retries: Option<u32>,
     /// If set to true, messages will be received in the order they were sent
     pub ordered: bool,
+    /// When set, messages not acked before this time expire and are no longer resent,
+    /// the expired messages are replaced by an empty message to keep the channel in sync.
+    /// Empty messages are discarded when received, so they cannot be sent in this channel.
+    pub message_ttl: Option<Duration>,
+    /// When set, messages are not accepted when the bytes of the messages waiting
+    /// to be sent or acked are above this limit.
+    pub max_queued_bytes: Option<usize>,
+    /// If set to true, in debug builds the receive channel errors with [ChannelError::ReliableChannelOrderViolation]
+    /// when it receives a message that breaks its ordering, like a message id already received with another payload.
+    /// Empty messages replacing the expired ones are exempt.
+    /// Useful to catch protocol bugs in development, it has no effect in release builds.
+    pub strict: bool,
 }
 
 #[derive(Debug)]
@@ -61,11 +85,20 @@ pub(crate) struct SendReliableChannel {
     packet_budget: u64,
     max_message_size: u64,
     message_resend_time: Duration,
+    // Configured resend time, the lower bound of the adaptive resend time
+    min_resend_time: Duration,
+    adaptive_resend_time: bool,
+    max_retries: Option<u32>,
+    message_ttl: Option<Duration>,
+    max_queued_bytes: Option<usize>,
+    queued_bytes: usize,
     packets_sent: SequenceBuffer<PacketSent>,
     messages_send: SequenceBuffer<ReliableMessageSent>,
     send_message_id: u16,
     num_messages_sent: u64,
     oldest_unacked_message_id: u16,
+    delivered_messages: Vec<u16>,
+    expired_messages: Vec<u16>,
     error: Option<ChannelError>,
 }
 
@@ -86,6 +119,8 @@ pub(crate) struct ReceiveReliableChannel {
     awaiting_message_id: u16,
     num_messages_received: u64,
     receive_order: ReceiveOrder,
+    discard_empty_messages: bool,
+    strict: bool,
     error: Option<ChannelError>,
 }
 
@@ -96,12 +131,16 @@ impl ReliableMessage {
 }
 
 impl ReliableMessageSent {
-    fn new(reliable_message: ReliableMessage, resend_time: Duration, current_time: Duration) -> Self {
+    fn new(reliable_message: ReliableMessage, priority: u8, resend_time: Duration, current_time: Duration) -> Self {
         let mut resend_timer = Timer::new(current_time, resend_time);
         resend_timer.finish();
         Self {
             reliable_message,
             resend_timer,
+            num_sends: 0,
+            last_sequence: 0,
+            priority,
+            send_time: current_time,
         }
     }
 }
@@ -122,7 +161,12 @@ impl Default for ReliableChannelConfig {
             packet_budget: 6000,
             max_message_size: 3000,
             message_resend_time: Duration::from_millis(200),
+            adaptive_resend_time: false,
+            max_retries: None,
             ordered: false,
+            message_ttl: None,
+            max_queued_bytes: None,
+            strict: false,
         }
     }
 }
@@ -143,9 +187,17 @@ impl SendReliableChannel {
             max_message_size: config.max_message_size,
             send_message_id: 0,
             oldest_unacked_message_id: 0,
+            delivered_messages: vec![],
+            expired_messages: vec![],
             packets_sent: SequenceBuffer::with_capacity(config.sent_packet_buffer_size),
             messages_send: SequenceBuffer::with_capacity(config.message_send_queue_size),
             message_resend_time: config.message_resend_time,
+            min_resend_time: config.message_resend_time,
+            adaptive_resend_time: config.adaptive_resend_time,
+            max_retries: config.max_retries,
+            message_ttl: config.message_ttl,
+            max_queued_bytes: config.max_queued_bytes,
+            queued_bytes: 0,
             num_messages_sent: 0,
             error: None,
         }
@@ -162,14 +214,46 @@ impl SendChannel for SendReliableChannel {
         let mut messages: Vec<Payload> = vec![];
         let mut message_ids: Vec<u16> = vec![];
 
+        // Messages ready to be sent, from the highest to the lowest priority
+        let mut ready_messages: Vec<(u16, u8)> = vec![];
         for i in 0..self.messages_send.size() {
             let message_id = self.oldest_unacked_message_id.wrapping_add(i as u16);
-            let message_send = self.messages_send.get_mut(message_id);
-            if let Some(message_send) = message_send {
+            if let Some(message_send) = self.messages_send.get_mut(message_id) {
+                message_send.resend_timer.set_duration(self.message_resend_time);
                 if !message_send.resend_timer.is_finished(current_time) {
                     continue;
                 }
 
+                if let Some(message_ttl) = self.message_ttl {
+                    let payload = &mut message_send.reliable_message.payload;
+                    if !payload.is_empty() && current_time - message_send.send_time >= message_ttl {
+                        log::debug!("Reliable message {} in channel {} expired", message_id, self.channel_id);
+                        self.queued_bytes -= payload.len();
+                        *payload = Bytes::new();
+                        self.expired_messages.push(message_id);
+                    }
+                }
+
+                if let Some(max_retries) = self.max_retries {
+                    if message_send.num_sends > max_retries {
+                        log::error!(
+                            "Reliable message {} in channel {} was not acked after {} retries",
+                            message_id,
+                            self.channel_id,
+                            max_retries
+                        );
+                        self.error = Some(ChannelError::ReliableMessageMaxRetries);
+                        return None;
+                    }
+                }
+
+                ready_messages.push((message_id, message_send.priority));
+            }
+        }
+        ready_messages.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        for (message_id, _) in ready_messages {
+            if let Some(message_send) = self.messages_send.get_mut(message_id) {
                 let serialized_size = match bincode::options().serialized_size(&message_send.reliable_message) {
                     Ok(size) => size,
                     Err(e) => {
@@ -182,6 +266,8 @@ impl SendChannel for SendReliableChannel {
                 if serialized_size <= available_bytes {
                     available_bytes -= serialized_size;
                     message_send.resend_timer.reset(current_time);
+                    message_send.num_sends += 1;
+                    message_send.last_sequence = sequence;
                     message_ids.push(message_id);
                     let message = match bincode::options().serialize(&message_send.reliable_message) {
                         Ok(message) => message,
@@ -217,8 +303,13 @@ impl SendChannel for SendReliableChannel {
             sent_packet.acked = true;
 
             for &message_id in sent_packet.messages_id.iter() {
-                if self.messages_send.exists(message_id) {
-                    self.messages_send.remove(message_id);
+                if let Some(message_send) = self.messages_send.remove(message_id) {
+                    self.queued_bytes -= message_send.reliable_message.payload.len();
+                    // Expired messages are replaced by an empty message, they were already reported as expired
+                    let expired = self.message_ttl.is_some() && message_send.reliable_message.payload.is_empty();
+                    if !expired {
+                        self.delivered_messages.push(message_id);
+                    }
                 }
             }
 
@@ -231,7 +322,27 @@ impl SendChannel for SendReliableChannel {
         }
     }
 
+    fn process_nack(&mut self, nack: u16) {
+        let sent_packet = match self.packets_sent.get(nack) {
+            Some(sent_packet) if !sent_packet.acked => sent_packet,
+            _ => return,
+        };
+
+        for &message_id in sent_packet.messages_id.iter() {
+            if let Some(message_send) = self.messages_send.get_mut(message_id) {
+                // Messages already resent in a newer packet wait for that packet
+                if message_send.last_sequence == nack {
+                    message_send.resend_timer.finish();
+                }
+            }
+        }
+    }
+
     fn send_message(&mut self, payload: Bytes, current_time: Duration) {
+        self.send_message_with_priority(payload, 0, current_time);
+    }
+
+    fn send_message_with_priority(&mut self, payload: Bytes, priority: u8, current_time: Duration) {
         if self.error.is_some() {
             return;
         }
@@ -253,9 +364,10 @@ impl SendChannel for SendReliableChannel {
         }
 
         self.send_message_id = self.send_message_id.wrapping_add(1);
+        self.queued_bytes += payload.len();
 
         let reliable_message = ReliableMessage::new(message_id, payload);
-        let entry = ReliableMessageSent::new(reliable_message, self.message_resend_time, current_time);
+        let entry = ReliableMessageSent::new(reliable_message, priority, self.message_resend_time, current_time);
         self.messages_send.insert(message_id, entry);
 
         self.num_messages_sent += 1;
@@ -265,6 +377,54 @@ impl SendChannel for SendReliableChannel {
         self.messages_send.available(self.send_message_id)
     }
 
+    fn has_messages_ready(&self, current_time: Duration) -> bool {
+        if !self.has_messages_to_send() || self.error.is_some() {
+            return false;
+        }
+
+        self.messages_send
+            .entries()
+            .any(|message| message.resend_timer.is_finished(current_time))
+    }
+
+    fn pending_messages(&self) -> usize {
+        self.messages_send.entries().count()
+    }
+
+    fn pending_bytes(&self) -> usize {
+        self.queued_bytes
+    }
+
+    fn max_queued_bytes(&self) -> Option<usize> {
+        self.max_queued_bytes
+    }
+
+    fn max_message_size(&self) -> u64 {
+        self.max_message_size
+    }
+
+    fn accepts_empty_messages(&self) -> bool {
+        self.message_ttl.is_none()
+    }
+
+    fn set_retransmission_timeout(&mut self, timeout: Duration) {
+        if self.adaptive_resend_time {
+            self.message_resend_time = timeout.max(self.min_resend_time);
+        }
+    }
+
+    fn next_message_id(&self) -> Option<u16> {
+        Some(self.send_message_id)
+    }
+
+    fn take_delivered_messages(&mut self) -> Vec<u16> {
+        std::mem::take(&mut self.delivered_messages)
+    }
+
+    fn take_expired_messages(&mut self) -> Vec<u16> {
+        std::mem::take(&mut self.expired_messages)
+    }
+
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
@@ -288,6 +448,8 @@ impl ReceiveReliableChannel {
             num_messages_received: 0,
             messages_received: SequenceBuffer::with_capacity(config.message_receive_queue_size),
             receive_order,
+            discard_empty_messages: config.message_ttl.is_some(),
+            strict: cfg!(debug_assertions) && config.strict,
             error: None,
         }
     }
@@ -300,7 +462,10 @@ impl ReceiveChannel for ReceiveReliableChannel {
         }
 
         for message in messages.iter() {
-            match bincode::options().deserialize::<ReliableMessage>(message) {
+            match bincode::options()
+                .with_limit(message.len() as u64)
+                .deserialize::<ReliableMessage>(message)
+            {
                 Ok(message) => {
                     if message.payload.len() as u64 > self.max_message_size {
                         log::error!(
@@ -323,6 +488,23 @@ impl ReceiveChannel for ReceiveReliableChannel {
                         self.error = Some(ChannelError::ReliableChannelOutOfSync);
                     }
 
+                    if self.strict {
+                        if let Some(received_message) = self.messages_received.get(message.id) {
+                            // The empty resend of an expired message replaces the original message
+                            let expired =
+                                self.discard_empty_messages && (message.payload.is_empty() || received_message.payload.is_empty());
+                            if !expired && received_message.payload != message.payload {
+                                log::error!(
+                                    "Received reliable message {} in channel {} with a different payload than the one already received",
+                                    message.id,
+                                    self.channel_id
+                                );
+                                self.error = Some(ChannelError::ReliableChannelOrderViolation);
+                                return;
+                            }
+                        }
+                    }
+
                     match &mut self.receive_order {
                         ReceiveOrder::Ordered => {
                             if !self.messages_received.exists(message.id) {
@@ -335,7 +517,7 @@ impl ReceiveChannel for ReceiveReliableChannel {
                         } => {
                             if !received_messages.exists(message.id) {
                                 received_messages.insert(message.id, ());
-                                if *most_recent_message_id < message.id {
+                                if sequence_greater_than(message.id, *most_recent_message_id) {
                                     *most_recent_message_id = message.id;
                                 }
                                 self.messages_received.insert(message.id, message);
@@ -353,6 +535,24 @@ impl ReceiveChannel for ReceiveReliableChannel {
     }
 
     fn receive_message(&mut self) -> Option<Payload> {
+        loop {
+            let message = self.receive_next_message()?;
+            // Empty messages replace the expired ones
+            if self.discard_empty_messages && message.is_empty() {
+                continue;
+            }
+
+            return Some(message);
+        }
+    }
+
+    fn error(&self) -> Option<ChannelError> {
+        self.error
+    }
+}
+
+impl ReceiveReliableChannel {
+    fn receive_next_message(&mut self) -> Option<Payload> {
         if self.error.is_some() {
             return None;
         }
@@ -367,10 +567,12 @@ impl ReceiveChannel for ReceiveReliableChannel {
                 self.awaiting_message_id = self.awaiting_message_id.wrapping_add(1);
                 self.num_messages_received += 1;
 
-                self.messages_received.remove(current_message_id).map(|m| m.payload.to_vec())
+                let message = self.messages_received.remove(current_message_id)?;
+                Some(message.payload.to_vec())
             }
             ReceiveOrder::Unordered {
-                most_recent_message_id, ..
+                received_messages,
+                most_recent_message_id,
             } => {
                 let mut current_message_id = self.awaiting_message_id;
                 let max_received_message_id = most_recent_message_id.wrapping_add(1);
@@ -380,22 +582,25 @@ impl ReceiveChannel for ReceiveReliableChannel {
                         continue;
                     }
 
-                    if self.awaiting_message_id == current_message_id {
+                    self.num_messages_received += 1;
+                    let message = self.messages_received.remove(current_message_id);
+
+                    // Skip the messages that were already delivered out of order,
+                    // so the awaiting message does not get stuck behind them.
+                    while sequence_less_than(self.awaiting_message_id, max_received_message_id)
+                        && received_messages.exists(self.awaiting_message_id)
+                        && !self.messages_received.exists(self.awaiting_message_id)
+                    {
                         self.awaiting_message_id = self.awaiting_message_id.wrapping_add(1);
                     }
 
-                    self.num_messages_received += 1;
-                    return self.messages_received.remove(current_message_id).map(|m| m.payload.to_vec());
+                    return message.map(|m| m.payload.to_vec());
                 }
 
                 None
             }
         }
     }
-
-    fn error(&self) -> Option<ChannelError> {
-        self.error
-    }
 }
 
 #[cfg(test)]
@@ -505,6 +710,87 @@ mod tests {
         assert!(!send_channel.has_messages_to_send());
     }
 
+    #[test]
+    fn unordered_exactly_once() {
+        let config = ReliableChannelConfig {
+            ordered: false,
+            message_receive_queue_size: 16,
+            ..Default::default()
+        };
+        let mut receive_channel = ReceiveReliableChannel::new(config);
+        let serialize = |id: u16| {
+            bincode::options()
+                .serialize(&ReliableMessage::new(id, vec![id as u8].into()))
+                .unwrap()
+        };
+
+        // Receive each pair of messages swapped, more times than the receive queue size
+        for id in (0..64u16).step_by(2) {
+            receive_channel.process_messages(vec![serialize(id + 1)]);
+            assert_eq!(receive_channel.receive_message().unwrap(), vec![id as u8 + 1]);
+
+            receive_channel.process_messages(vec![serialize(id), serialize(id + 1)]);
+            assert_eq!(receive_channel.receive_message().unwrap(), vec![id as u8]);
+            assert!(receive_channel.receive_message().is_none());
+        }
+
+        assert!(receive_channel.error().is_none());
+        assert_eq!(receive_channel.num_messages_received, 64);
+    }
+
+    #[test]
+    fn message_ttl() {
+        let mut current_time = Duration::ZERO;
+        let config = ReliableChannelConfig {
+            message_ttl: Some(Duration::from_millis(300)),
+            ordered: true,
+            ..Default::default()
+        };
+        let mut send_channel = SendReliableChannel::new(config.clone());
+        let mut receive_channel = ReceiveReliableChannel::new(config);
+
+        let first_message = TestMessages::First.serialize();
+        send_channel.send_message(first_message, current_time);
+        send_channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+
+        // Message lost and expired, an empty message is sent in its place
+        current_time += Duration::from_millis(300);
+        let channel_data = send_channel.get_messages_to_send(u64::MAX, 1, current_time).unwrap();
+        assert_eq!(send_channel.take_expired_messages(), vec![0]);
+        receive_channel.process_messages(channel_data.messages);
+        assert!(receive_channel.receive_message().is_none());
+
+        // The empty message is acked, but the expired message is not reported as delivered
+        send_channel.process_ack(1);
+        assert!(send_channel.take_delivered_messages().is_empty());
+
+        // Messages after the expired one are still received in order
+        let second_message = TestMessages::Second.serialize();
+        send_channel.send_message(second_message.clone(), current_time);
+        let channel_data = send_channel.get_messages_to_send(u64::MAX, 2, current_time).unwrap();
+        receive_channel.process_messages(channel_data.messages);
+        assert_eq!(receive_channel.receive_message().unwrap(), second_message);
+    }
+
+    #[test]
+    fn pending_messages() {
+        let current_time = Duration::ZERO;
+        let mut send_channel = SendReliableChannel::new(ReliableChannelConfig::default());
+
+        send_channel.send_message(vec![0u8; 10].into(), current_time);
+        send_channel.send_message(vec![0u8; 20].into(), current_time);
+        assert_eq!(send_channel.pending_messages(), 2);
+        assert_eq!(send_channel.pending_bytes(), 30);
+
+        // Sent messages are pending until acked
+        send_channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+        assert_eq!(send_channel.pending_messages(), 2);
+
+        send_channel.process_ack(0);
+        assert_eq!(send_channel.pending_messages(), 0);
+        assert_eq!(send_channel.pending_bytes(), 0);
+    }
+
     #[test]
     fn over_budget() {
         let current_time = Duration::ZERO;
@@ -529,6 +815,31 @@ mod tests {
         assert_eq!(channel_data.messages.len(), 1);
     }
 
+    #[test]
+    fn message_priority() {
+        let current_time = Duration::ZERO;
+        let config = ReliableChannelConfig::default();
+        let mut send_channel = SendReliableChannel::new(config.clone());
+        let mut receive_channel = ReceiveReliableChannel::new(config);
+
+        let low_message = TestMessages::First.serialize();
+        let high_message = TestMessages::Second.serialize();
+        let message = ReliableMessage::new(0, low_message.clone());
+        let message_size = bincode::options().serialized_size(&message).unwrap();
+
+        send_channel.send_message_with_priority(low_message.clone(), 0, current_time);
+        send_channel.send_message_with_priority(high_message.clone(), 10, current_time);
+
+        let channel_data = send_channel.get_messages_to_send(message_size, 0, current_time).unwrap();
+        receive_channel.process_messages(channel_data.messages);
+        assert_eq!(receive_channel.receive_message().unwrap(), high_message);
+
+        // Lower priority messages are still sent later
+        let channel_data = send_channel.get_messages_to_send(message_size, 1, current_time).unwrap();
+        receive_channel.process_messages(channel_data.messages);
+        assert_eq!(receive_channel.receive_message().unwrap(), low_message);
+    }
+
     #[test]
     fn resend_message() {
         let mut current_time = Duration::ZERO;
@@ -552,6 +863,60 @@ mod tests {
         assert_eq!(channel_data.messages.len(), 1);
     }
 
+    #[test]
+    fn adaptive_resend_message() {
+        let mut current_time = Duration::ZERO;
+        let config = ReliableChannelConfig {
+            message_resend_time: Duration::from_millis(100),
+            adaptive_resend_time: true,
+            ..Default::default()
+        };
+        let mut channel = SendReliableChannel::new(config);
+
+        channel.send_message(TestMessages::First.serialize(), current_time);
+        channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+
+        channel.set_retransmission_timeout(Duration::from_millis(300));
+        current_time += Duration::from_millis(200);
+        assert!(channel.get_messages_to_send(u64::MAX, 1, current_time).is_none());
+
+        current_time += Duration::from_millis(100);
+        let channel_data = channel.get_messages_to_send(u64::MAX, 2, current_time).unwrap();
+        assert_eq!(channel_data.messages.len(), 1);
+
+        // A retransmission timeout below the configured resend time is clamped to it
+        channel.set_retransmission_timeout(Duration::from_millis(10));
+        current_time += Duration::from_millis(50);
+        assert!(channel.get_messages_to_send(u64::MAX, 3, current_time).is_none());
+
+        current_time += Duration::from_millis(50);
+        let channel_data = channel.get_messages_to_send(u64::MAX, 4, current_time).unwrap();
+        assert_eq!(channel_data.messages.len(), 1);
+    }
+
+    #[test]
+    fn max_retries() {
+        let mut current_time = Duration::ZERO;
+        let message_resend_time = Duration::from_millis(100);
+        let config = ReliableChannelConfig {
+            message_resend_time,
+            max_retries: Some(1),
+            ..Default::default()
+        };
+        let mut channel = SendReliableChannel::new(config);
+
+        channel.send_message(TestMessages::First.serialize(), current_time);
+        channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+
+        current_time += message_resend_time;
+        channel.get_messages_to_send(u64::MAX, 1, current_time).unwrap();
+        assert!(channel.error().is_none());
+
+        current_time += message_resend_time;
+        assert!(channel.get_messages_to_send(u64::MAX, 2, current_time).is_none());
+        assert_eq!(channel.error(), Some(ChannelError::ReliableMessageMaxRetries));
+    }
+
     #[test]
     fn out_of_sync() {
         let current_time = Duration::ZERO;
@@ -579,4 +944,56 @@ mod tests {
         receive_channel.process_messages(second_channel_data.messages);
         assert!(matches!(receive_channel.error(), Some(ChannelError::ReliableChannelOutOfSync)));
     }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn strict_order_violation() {
+        let config = ReliableChannelConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let mut receive_channel = ReceiveReliableChannel::new(config);
+        let serialize = |message: &ReliableMessage| bincode::options().serialize(message).unwrap();
+
+        let message = ReliableMessage::new(0, TestMessages::First.serialize());
+        receive_channel.process_messages(vec![serialize(&message), serialize(&message)]);
+        assert!(receive_channel.error().is_none());
+
+        let message = ReliableMessage::new(0, TestMessages::Second.serialize());
+        receive_channel.process_messages(vec![serialize(&message)]);
+        assert_eq!(receive_channel.error(), Some(ChannelError::ReliableChannelOrderViolation));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn strict_ordered_delivery() {
+        let config = ReliableChannelConfig {
+            strict: true,
+            ordered: true,
+            message_ttl: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+        let mut receive_channel = ReceiveReliableChannel::new(config);
+        let serialize = |message: &ReliableMessage| bincode::options().serialize(message).unwrap();
+
+        // The empty message replacing an expired message is not a violation
+        let first = ReliableMessage::new(0, TestMessages::First.serialize());
+        let expired = ReliableMessage::new(0, Bytes::new());
+        let second = ReliableMessage::new(1, TestMessages::Second.serialize());
+        receive_channel.process_messages(vec![serialize(&first), serialize(&expired), serialize(&second)]);
+        assert!(receive_channel.error().is_none());
+        assert_eq!(receive_channel.receive_message().unwrap(), TestMessages::First.serialize());
+        assert_eq!(receive_channel.receive_message().unwrap(), TestMessages::Second.serialize());
+
+        // A reordered resend with a different payload breaks the order
+        let third = ReliableMessage::new(3, TestMessages::First.serialize());
+        let second = ReliableMessage::new(2, TestMessages::Second.serialize());
+        receive_channel.process_messages(vec![serialize(&third), serialize(&second)]);
+        assert!(receive_channel.error().is_none());
+
+        let third = ReliableMessage::new(3, TestMessages::Third.serialize());
+        receive_channel.process_messages(vec![serialize(&third)]);
+        assert_eq!(receive_channel.error(), Some(ChannelError::ReliableChannelOrderViolation));
+        assert!(receive_channel.receive_message().is_none());
+    }
 }
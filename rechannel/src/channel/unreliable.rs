@@ -35,6 +35,12 @@ pub struct UnreliableChannelConfig {
     /// If this is true, only most recent messages will be received,
     /// old messages received out of order are dropped.
     pub sequenced: bool,
+    /// If this is true, only the latest message is kept in the send queue, sending a message
+    /// replaces the one not sent yet. Useful for state snapshots where only the newest matters.
+    pub latest_only: bool,
+    /// When set, messages are not accepted when the bytes of the messages waiting
+    /// to be sent are above this limit.
+    pub max_queued_bytes: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -49,7 +55,12 @@ pub(crate) struct SendUnreliableChannel {
     packet_budget: u64,
     max_message_size: u64,
     message_send_queue_size: usize,
+    max_queued_bytes: Option<usize>,
+    latest_only: bool,
     messages_to_send: VecDeque<Bytes>,
+    // Messages that didn't fit in the packets generated at the last send time
+    unsent_messages: usize,
+    last_send_time: Duration,
     send_order: SendOrder,
     error: Option<ChannelError>,
 }
@@ -79,6 +90,8 @@ impl Default for UnreliableChannelConfig {
             message_send_queue_size: 256,
             message_receive_queue_size: 256,
             sequenced: false,
+            latest_only: false,
+            max_queued_bytes: None,
         }
     }
 }
@@ -96,7 +109,11 @@ impl SendUnreliableChannel {
             packet_budget: config.packet_budget,
             max_message_size: config.max_message_size,
             message_send_queue_size: config.message_send_queue_size,
+            max_queued_bytes: config.max_queued_bytes,
+            latest_only: config.latest_only,
             messages_to_send: VecDeque::with_capacity(config.message_send_queue_size),
+            unsent_messages: 0,
+            last_send_time: Duration::ZERO,
             send_order,
             error: None,
         }
@@ -104,23 +121,37 @@ impl SendUnreliableChannel {
 }
 
 impl SendChannel for SendUnreliableChannel {
-    fn get_messages_to_send(&mut self, mut available_bytes: u64, _sequence: u16, _current_time: Duration) -> Option<ChannelPacketData> {
+    fn get_messages_to_send(&mut self, mut available_bytes: u64, _sequence: u16, current_time: Duration) -> Option<ChannelPacketData> {
         if self.error.is_some() {
             return None;
         }
 
+        // Messages that didn't fit are kept for the other packets generated at the same time,
+        // like when the packets are coalesced or flushed, and dropped once the time advances.
+        if current_time != self.last_send_time {
+            let unsent_messages = self.unsent_messages.min(self.messages_to_send.len());
+            if unsent_messages > 0 {
+                log::debug!(
+                    "Dropped {} unsent messages in unreliable channel {}",
+                    unsent_messages,
+                    self.channel_id
+                );
+                self.messages_to_send.drain(..unsent_messages);
+            }
+            self.last_send_time = current_time;
+        }
+
         let mut messages = vec![];
         available_bytes = available_bytes.min(self.packet_budget);
 
-        while let Some(message) = self.messages_to_send.pop_front() {
+        while let Some(payload) = self.messages_to_send.pop_front() {
             let message = match &mut self.send_order {
-                SendOrder::None => message.to_vec(),
+                SendOrder::None => payload.to_vec(),
                 SendOrder::Sequenced { send_message_id } => {
                     let sequenced_message = SequencedMessage {
                         id: *send_message_id,
-                        payload: message,
+                        payload: payload.clone(),
                     };
-                    *send_message_id = send_message_id.wrapping_add(1);
 
                     match bincode::options().serialize(&sequenced_message) {
                         Ok(message) => message,
@@ -135,13 +166,18 @@ impl SendChannel for SendUnreliableChannel {
 
             let message_size = message.len() as u64;
             if message_size > available_bytes {
-                // No available bytes, drop message
-                continue;
+                // No available bytes, the message and the ones after it wait for the next packet
+                self.messages_to_send.push_front(payload);
+                break;
             }
 
+            if let SendOrder::Sequenced { send_message_id } = &mut self.send_order {
+                *send_message_id = send_message_id.wrapping_add(1);
+            }
             messages.push(message);
             available_bytes -= message_size;
         }
+        self.unsent_messages = self.messages_to_send.len();
 
         if messages.is_empty() {
             return None;
@@ -170,6 +206,11 @@ impl SendChannel for SendUnreliableChannel {
             return;
         }
 
+        if self.latest_only {
+            self.messages_to_send.clear();
+            self.unsent_messages = 0;
+        }
+
         if self.messages_to_send.len() >= self.message_send_queue_size {
             self.error = Some(ChannelError::SendQueueFull);
             log::warn!("Unreliable channel {} has reached the maximum queue size", self.channel_id);
@@ -183,6 +224,26 @@ impl SendChannel for SendUnreliableChannel {
         self.messages_to_send.len() < self.message_send_queue_size
     }
 
+    fn has_messages_ready(&self, _current_time: Duration) -> bool {
+        self.error.is_none() && !self.messages_to_send.is_empty()
+    }
+
+    fn pending_messages(&self) -> usize {
+        self.messages_to_send.len()
+    }
+
+    fn pending_bytes(&self) -> usize {
+        self.messages_to_send.iter().map(|message| message.len()).sum()
+    }
+
+    fn max_queued_bytes(&self) -> Option<usize> {
+        self.max_queued_bytes
+    }
+
+    fn max_message_size(&self) -> u64 {
+        self.max_message_size
+    }
+
     fn error(&self) -> Option<ChannelError> {
         self.error
     }
@@ -242,7 +303,10 @@ impl ReceiveChannel for ReceiveUnreliableChannel {
 
             let message = match &mut self.receive_order {
                 ReceiveOrder::None => message,
-                ReceiveOrder::Sequenced { most_recent_message_id } => match bincode::options().deserialize::<SequencedMessage>(&message) {
+                ReceiveOrder::Sequenced { most_recent_message_id } => match bincode::options()
+                    .with_limit(message.len() as u64)
+                    .deserialize::<SequencedMessage>(&message)
+                {
                     Ok(sequenced_message) => {
                         if sequence_less_than(sequenced_message.id, *most_recent_message_id) {
                             continue;
@@ -344,4 +408,47 @@ mod tests {
         let received_message = receive_channel.receive_message();
         assert!(received_message.is_none());
     }
+
+    #[test]
+    fn unsent_messages() {
+        let mut send_channel = SendUnreliableChannel::new(UnreliableChannelConfig::default());
+        for i in 0..3 {
+            send_channel.send_message(Bytes::from(vec![i; 100]), Duration::ZERO);
+        }
+
+        // The messages that don't fit are kept for the next packets generated at the same time
+        let channel_data = send_channel.get_messages_to_send(250, 0, Duration::ZERO).unwrap();
+        assert_eq!(channel_data.messages, vec![vec![0; 100], vec![1; 100]]);
+        let channel_data = send_channel.get_messages_to_send(250, 1, Duration::ZERO).unwrap();
+        assert_eq!(channel_data.messages, vec![vec![2; 100]]);
+
+        // And dropped once the time advances
+        send_channel.send_message(Bytes::from(vec![3; 100]), Duration::ZERO);
+        send_channel.send_message(Bytes::from(vec![4; 100]), Duration::ZERO);
+        assert!(send_channel.get_messages_to_send(50, 2, Duration::ZERO).is_none());
+        send_channel.send_message(Bytes::from(vec![5; 100]), Duration::ZERO);
+        let channel_data = send_channel.get_messages_to_send(u64::MAX, 3, Duration::from_millis(1)).unwrap();
+        assert_eq!(channel_data.messages, vec![vec![5; 100]]);
+    }
+
+    #[test]
+    fn latest_only() {
+        let current_time = Duration::ZERO;
+        let config = UnreliableChannelConfig {
+            latest_only: true,
+            ..Default::default()
+        };
+        let mut send_channel = SendUnreliableChannel::new(config.clone());
+        let mut receive_channel = ReceiveUnreliableChannel::new(config);
+
+        for i in 0..5 {
+            send_channel.send_message(Bytes::from(vec![i]), current_time);
+        }
+        assert_eq!(send_channel.pending_messages(), 1);
+
+        let channel_data = send_channel.get_messages_to_send(u64::MAX, 0, current_time).unwrap();
+        receive_channel.process_messages(channel_data.messages);
+        assert_eq!(receive_channel.receive_message().unwrap(), vec![4]);
+        assert!(receive_channel.receive_message().is_none());
+    }
 }
@@ -17,6 +17,8 @@ pub enum DisconnectionReason {
     SendChannelError { channel_id: u8, error: ChannelError },
     /// Error occurred in a receive channel
     ReceiveChannelError { channel_id: u8, error: ChannelError },
+    /// Connection terminated with a reason defined by the application
+    Custom(u8),
 }
 
 /// Possibles errors that can occur in a channel.
@@ -35,6 +37,10 @@ pub enum ChannelError {
     ReceivedMessageAboveMaxSize,
     /// Received an invalid slice message in a block channel.
     InvalidSliceMessage,
+    /// A reliable message was resent more times than the channel max retries.
+    ReliableMessageMaxRetries,
+    /// A strict reliable channel received a message that breaks its ordering.
+    ReliableChannelOrderViolation,
 }
 
 impl fmt::Display for ChannelError {
@@ -48,6 +54,8 @@ impl fmt::Display for ChannelError {
             SentMessageAboveMaxSize => write!(fmt, "sent message above the channel max message size"),
             ReceivedMessageAboveMaxSize => write!(fmt, "received message above the channel max message size"),
             InvalidSliceMessage => write!(fmt, "received an invalid slice message in a block channel"),
+            ReliableMessageMaxRetries => write!(fmt, "reliable message was not acked after the max retries"),
+            ReliableChannelOrderViolation => write!(fmt, "strict reliable channel received a message that breaks its ordering"),
         }
     }
 }
@@ -62,21 +70,113 @@ impl fmt::Display for DisconnectionReason {
             InvalidChannelId(id) => write!(fmt, "received message with invalid channel {}", id),
             SendChannelError { channel_id, error } => write!(fmt, "send channel {} with error: {}", channel_id, error),
             ReceiveChannelError { channel_id, error } => write!(fmt, "receive channel {} with error: {}", channel_id, error),
+            Custom(reason) => write!(fmt, "connection terminated with custom reason {}", reason),
         }
     }
 }
 
 impl std::error::Error for ChannelError {}
 
+/// Possible errors of an invalid connection configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The fragment size is zero
+    ZeroFragmentSize,
+    /// The max packet size needs more fragments than the max fragments allowed
+    TooManyFragments {
+        max_packet_size: u64,
+        num_fragments: u64,
+        max_fragments: u8,
+    },
+    /// The fragment threshold is above the max packet size, so packets are never fragmented
+    FragmentAboveMaxPacketSize { fragment_above: u64, max_packet_size: u64 },
+    /// The fragments with the packet header don't fit in the min size of the MTU discovery,
+    /// so they could be above the discovered MTU
+    FragmentSizeAboveMtu { fragment_size: usize, min_mtu: u64 },
+    /// A packet buffer size is not a power of two, required to wrap around the packet sequence
+    BufferSizeNotPowerOfTwo { buffer: &'static str, size: usize },
+    /// The ack window size is above 64 packets
+    AckWindowTooLarge(u8),
+    /// More than one channel was configured with the same id
+    DuplicatedChannelId(u8),
+    /// A smoothing factor is not between 0.0 and 1.0
+    InvalidSmoothingFactor { factor: &'static str },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ConfigError::*;
+
+        match *self {
+            ZeroFragmentSize => write!(fmt, "fragment size cannot be zero"),
+            TooManyFragments {
+                max_packet_size,
+                num_fragments,
+                max_fragments,
+            } => write!(
+                fmt,
+                "max packet size of {} bytes needs {} fragments, the limit is {}. \
+                Reduce the max packet size or increase the fragment size",
+                max_packet_size, num_fragments, max_fragments
+            ),
+            FragmentAboveMaxPacketSize {
+                fragment_above,
+                max_packet_size,
+            } => write!(
+                fmt,
+                "fragment above of {} bytes should not be greater than the max packet size of {} bytes",
+                fragment_above, max_packet_size
+            ),
+            FragmentSizeAboveMtu { fragment_size, min_mtu } => write!(
+                fmt,
+                "fragment size of {} bytes with the packet header should fit in the MTU discovery min size of {} bytes",
+                fragment_size, min_mtu
+            ),
+            BufferSizeNotPowerOfTwo { buffer, size } => write!(fmt, "{} size should be a power of two, got {}", buffer, size),
+            AckWindowTooLarge(size) => write!(fmt, "ack window size should be at most 64, got {}", size),
+            DuplicatedChannelId(id) => write!(fmt, "more than one channel configured with id {}", id),
+            InvalidSmoothingFactor { factor } => write!(fmt, "{} should be between 0.0 and 1.0", factor),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 #[derive(Debug)]
 pub enum RechannelError {
     /// The channel has reached the maximum messages capacity defined in the channel configuration
     ChannelMaxMessagesLimit,
     ClientDisconnected(DisconnectionReason),
     ClientNotFound,
+    /// Channel with given Id was not found
+    InvalidChannelId(u8),
+    /// Tried to send a message above the channel max message size
+    MessageTooLarge {
+        channel_id: u8,
+        size: usize,
+        max: u64,
+    },
+    /// The channel has reached the maximum queued bytes defined in the channel configuration
+    ChannelFull {
+        channel_id: u8,
+    },
+    /// Tried to send an empty message in a channel with a message TTL,
+    /// empty messages replace the expired ones in those channels
+    EmptyMessage {
+        channel_id: u8,
+    },
+    /// The channel does not track the delivery of its messages, only reliable channels do
+    DeliveryNotTracked(u8),
+    /// Tried to send an unreliable datagram with a packet above the fragmentation threshold
+    DatagramTooLarge {
+        size: usize,
+        max: u64,
+    },
     /// An error occurred when processing a fragmented packet
     FragmentError(FragmentError),
     BincodeError(bincode::Error),
+    /// An error occurred when (de)serializing a packet
+    SerializationError(crate::serialization::SerializationError),
 }
 
 impl std::error::Error for RechannelError {}
@@ -88,8 +188,19 @@ impl fmt::Display for RechannelError {
         match *self {
             ChannelMaxMessagesLimit => write!(fmt, "the channel has reached the maximum messages capacity"),
             ClientNotFound => write!(fmt, "client with given id was not found"),
+            InvalidChannelId(id) => write!(fmt, "channel with id {} was not found", id),
+            MessageTooLarge { channel_id, size, max } => write!(
+                fmt,
+                "message with {} bytes is above the max message size of {} bytes for channel {}",
+                size, max, channel_id
+            ),
+            ChannelFull { channel_id } => write!(fmt, "channel {} has reached the maximum queued bytes", channel_id),
+            EmptyMessage { channel_id } => write!(fmt, "channel {} does not accept empty messages", channel_id),
+            DeliveryNotTracked(id) => write!(fmt, "channel {} does not track the delivery of messages", id),
+            DatagramTooLarge { size, max } => write!(fmt, "datagram packet with {} bytes is above the max size of {} bytes", size, max),
             ClientDisconnected(reason) => write!(fmt, "client is disconnected: {}", reason),
             BincodeError(ref bincode_err) => write!(fmt, "{}", bincode_err),
+            SerializationError(ref serialization_err) => write!(fmt, "serialization error: {}", serialization_err),
             FragmentError(ref fragment_error) => write!(fmt, "{}", fragment_error),
         }
     }
@@ -101,6 +212,12 @@ impl From<bincode::Error> for RechannelError {
     }
 }
 
+impl From<crate::serialization::SerializationError> for RechannelError {
+    fn from(inner: crate::serialization::SerializationError) -> Self {
+        RechannelError::SerializationError(inner)
+    }
+}
+
 impl From<FragmentError> for RechannelError {
     fn from(inner: FragmentError) -> Self {
         RechannelError::FragmentError(inner)
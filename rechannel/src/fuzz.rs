@@ -0,0 +1,58 @@
+use crate::remote_connection::{ConnectionConfig, RemoteConnection};
+
+use std::time::Duration;
+
+/// Processes arbitrary bytes as a packet received by a connection with the default configuration.
+/// It never panics, whatever the input, so it can be used as a `cargo fuzz` target:
+///
+/// ```ignore
+/// fuzz_target!(|data: &[u8]| rechannel::fuzz::process_packet(data));
+/// ```
+pub fn process_packet(packet: &[u8]) {
+    let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+    process_packet_with_connection(&mut connection, packet);
+}
+
+/// Processes arbitrary bytes as a packet received by the connection, then receives the messages
+/// from all the channels and generates the packets to send. Calling it multiple times with the same
+/// connection fuzzes state kept between packets, like the fragments reassembly or the acks.
+pub fn process_packet_with_connection(connection: &mut RemoteConnection, packet: &[u8]) {
+    if connection.process_packet(packet).is_err() {
+        return;
+    }
+
+    for channel_id in connection.receive_channels_id() {
+        while connection.receive_message(channel_id).is_some() {}
+    }
+
+    connection.advance_time(Duration::from_millis(16));
+    if connection.update().is_ok() {
+        let _ = connection.get_packets_to_send();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_packets() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.send_message(0, vec![1u8; 10]);
+        connection.send_message(1, vec![2u8; 2000]);
+        let packets = connection.get_packets_to_send().unwrap();
+
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        for packet in packets.iter() {
+            for len in 0..packet.len() {
+                process_packet(&packet[..len]);
+            }
+
+            for i in 0..packet.len() {
+                let mut packet = packet.clone();
+                packet[i] = !packet[i];
+                process_packet_with_connection(&mut remote_connection, &packet);
+            }
+        }
+    }
+}
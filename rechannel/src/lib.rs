@@ -1,15 +1,27 @@
+#[cfg(feature = "tokio")]
+pub mod async_connection;
 pub mod channel;
 pub mod error;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+#[cfg(feature = "memory_transport")]
+pub mod memory_transport;
+pub mod observer;
 mod packet;
 mod reassembly_fragment;
+#[cfg(feature = "recorder")]
+pub mod recorder;
 pub mod remote_connection;
 mod sequence_buffer;
+pub mod serialization;
 pub mod server;
+#[cfg(feature = "simulator")]
+pub mod simulator;
 mod timer;
 
 pub use bytes::Bytes;
 pub use packet::disconnect_packet;
-pub use reassembly_fragment::FragmentConfig;
+pub use reassembly_fragment::{FragmentConfig, FragmentError, ReassemblyStats};
 
 use std::{fmt::Debug, hash::Hash};
 
@@ -0,0 +1,154 @@
+use crate::error::RechannelError;
+use crate::packet::Payload;
+use crate::remote_connection::RemoteConnection;
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// In-memory transport that behaves like a socket connected to another [MemoryTransport].
+/// Packets are delivered in the order they are sent, unless loss or reordering is injected,
+/// so tests using it are deterministic and do not depend on the OS network stack.
+#[derive(Debug)]
+pub struct MemoryTransport {
+    sender: Sender<Payload>,
+    receiver: Receiver<Payload>,
+    drop_next: usize,
+    hold_next: usize,
+    held_packets: Vec<Payload>,
+}
+
+impl MemoryTransport {
+    /// Creates two transports connected to each other.
+    pub fn pair() -> (Self, Self) {
+        let (sender_a, receiver_a) = mpsc::channel();
+        let (sender_b, receiver_b) = mpsc::channel();
+
+        (Self::new(sender_a, receiver_b), Self::new(sender_b, receiver_a))
+    }
+
+    fn new(sender: Sender<Payload>, receiver: Receiver<Payload>) -> Self {
+        Self {
+            sender,
+            receiver,
+            drop_next: 0,
+            hold_next: 0,
+            held_packets: vec![],
+        }
+    }
+
+    /// Sends a packet to the other transport.
+    /// Packets sent after the other transport is dropped are lost.
+    pub fn send(&mut self, packet: Payload) {
+        if self.drop_next > 0 {
+            self.drop_next -= 1;
+            return;
+        }
+
+        if self.hold_next > 0 {
+            self.hold_next -= 1;
+            self.held_packets.push(packet);
+            return;
+        }
+
+        let _ = self.sender.send(packet);
+    }
+
+    /// Returns the next packet received from the other transport.
+    pub fn recv(&mut self) -> Option<Payload> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Drops the next packets sent.
+    pub fn drop_next_packets(&mut self, count: usize) {
+        self.drop_next += count;
+    }
+
+    /// Holds back the next packets sent until [MemoryTransport::release_held_packets] is called,
+    /// so they are received after the packets sent in between.
+    pub fn hold_next_packets(&mut self, count: usize) {
+        self.hold_next += count;
+    }
+
+    /// Sends the packets held back, in the order they were sent.
+    pub fn release_held_packets(&mut self) {
+        for packet in self.held_packets.drain(..) {
+            let _ = self.sender.send(packet);
+        }
+    }
+
+    /// Sends the packets generated by the connection.
+    pub fn send_packets(&mut self, connection: &mut RemoteConnection) -> Result<(), RechannelError> {
+        for packet in connection.get_packets_to_send()? {
+            self.send(packet);
+        }
+
+        Ok(())
+    }
+
+    /// Processes all the packets received in the connection.
+    pub fn receive_packets(&mut self, connection: &mut RemoteConnection) -> Result<(), RechannelError> {
+        while let Some(packet) = self.recv() {
+            connection.process_packet(&packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::ReliableChannelConfig;
+    use crate::remote_connection::ConnectionConfig;
+
+    use std::time::Duration;
+
+    #[test]
+    fn loss_and_reorder() {
+        let config = ConnectionConfig {
+            send_channels_config: vec![ReliableChannelConfig {
+                ordered: true,
+                ..Default::default()
+            }
+            .into()],
+            receive_channels_config: vec![ReliableChannelConfig {
+                ordered: true,
+                ..Default::default()
+            }
+            .into()],
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+        let (mut transport, mut remote_transport) = MemoryTransport::pair();
+
+        transport.drop_next_packets(1);
+        connection.send_message(0, vec![1]);
+        transport.send_packets(&mut connection).unwrap();
+
+        transport.hold_next_packets(1);
+        connection.advance_time(Duration::from_millis(100));
+        connection.send_message(0, vec![2]);
+        transport.send_packets(&mut connection).unwrap();
+
+        connection.advance_time(Duration::from_millis(100));
+        connection.send_message(0, vec![3]);
+        transport.send_packets(&mut connection).unwrap();
+        transport.release_held_packets();
+
+        remote_transport.receive_packets(&mut remote_connection).unwrap();
+        remote_transport.send_packets(&mut remote_connection).unwrap();
+        transport.receive_packets(&mut connection).unwrap();
+
+        for _ in 0..3 {
+            connection.advance_time(Duration::from_millis(100));
+            connection.update().unwrap();
+            transport.send_packets(&mut connection).unwrap();
+            remote_transport.receive_packets(&mut remote_connection).unwrap();
+        }
+
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![1]);
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![2]);
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![3]);
+        assert!(remote_connection.receive_message(0).is_none());
+    }
+}
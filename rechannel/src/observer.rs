@@ -0,0 +1,104 @@
+use crate::error::DisconnectionReason;
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Reason why a received packet was discarded by the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DroppedPacket {
+    /// The packet was already received.
+    Duplicated { sequence: u16 },
+    /// The fragment was already received.
+    DuplicatedFragment { sequence: u16, fragment_id: u8 },
+    /// Incomplete packets were evicted from the reassembly buffer by newer packets.
+    EvictedFragments { count: usize },
+}
+
+/// Hooks for the events of a [RemoteConnection](crate::remote_connection::RemoteConnection),
+/// so diagnostics can be routed to a custom system instead of the log crate.
+/// The default implementations log the events, like the [LogObserver].
+/// The hooks take `&self`, use interior mutability to keep state in the observer.
+pub trait ConnectionObserver: Debug + Send + Sync {
+    /// Called when a received packet is discarded.
+    fn on_packet_dropped(&self, reason: DroppedPacket) {
+        match reason {
+            DroppedPacket::Duplicated { sequence } => log::trace!("Discarded duplicated packet {}", sequence),
+            DroppedPacket::DuplicatedFragment { sequence, fragment_id } => {
+                log::trace!("Discarded duplicated fragment {} of packet {}", fragment_id, sequence)
+            }
+            DroppedPacket::EvictedFragments { count } => log::debug!("Evicted {} incomplete packets from the reassembly buffer", count),
+        }
+    }
+
+    /// Called when a sent packet is acked by the remote, with the time since it was sent.
+    fn on_ack(&self, sequence: u16, rtt: Duration) {
+        log::trace!("Packet {} acked after {:?}", sequence, rtt);
+    }
+
+    /// Called when the connection is disconnected, locally or by the remote.
+    fn on_disconnected(&self, reason: DisconnectionReason) {
+        log::debug!("Connection disconnected: {}", reason);
+    }
+}
+
+/// Default observer, logs the events with the log crate.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogObserver;
+
+impl ConnectionObserver for LogObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote_connection::{ConnectionConfig, RemoteConnection};
+
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default, Clone)]
+    struct RecordObserver {
+        dropped: Arc<Mutex<Vec<DroppedPacket>>>,
+        acks: Arc<Mutex<Vec<u16>>>,
+        disconnections: Arc<Mutex<Vec<DisconnectionReason>>>,
+    }
+
+    impl ConnectionObserver for RecordObserver {
+        fn on_packet_dropped(&self, reason: DroppedPacket) {
+            self.dropped.lock().unwrap().push(reason);
+        }
+
+        fn on_ack(&self, sequence: u16, _rtt: Duration) {
+            self.acks.lock().unwrap().push(sequence);
+        }
+
+        fn on_disconnected(&self, reason: DisconnectionReason) {
+            self.disconnections.lock().unwrap().push(reason);
+        }
+    }
+
+    #[test]
+    fn observer_events() {
+        let observer = RecordObserver::default();
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.set_observer(observer.clone());
+        remote_connection.set_observer(observer.clone());
+
+        connection.send_message(0, vec![1, 2, 3]);
+        let packets = connection.get_packets_to_send().unwrap();
+        remote_connection.process_packet(&packets[0]).unwrap();
+        remote_connection.process_packet(&packets[0]).unwrap();
+        assert_eq!(*observer.dropped.lock().unwrap(), vec![DroppedPacket::Duplicated { sequence: 0 }]);
+
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap().iter() {
+            connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(*observer.acks.lock().unwrap(), vec![0]);
+
+        connection.disconnect();
+        assert_eq!(
+            *observer.disconnections.lock().unwrap(),
+            vec![DisconnectionReason::DisconnectedByClient]
+        );
+    }
+}
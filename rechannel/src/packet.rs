@@ -1,6 +1,7 @@
 use crate::error::DisconnectionReason;
 
 use bincode::Options;
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 pub type Payload = Vec<u8>;
@@ -14,7 +15,9 @@ pub(crate) struct ChannelPacketData {
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub(crate) struct AckData {
     pub ack: u16,
-    pub ack_bits: u32,
+    /// With the default varint encoding, ack bits within the first 32 bits
+    /// are serialized the same way as an u32.
+    pub ack_bits: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -35,6 +38,26 @@ pub(crate) enum Packet {
     Disconnect {
         reason: DisconnectionReason,
     },
+    // Unreliable payload sent outside the channels. Serialized like a Payload,
+    // but the queued payload is moved into the packet instead of copied.
+    Datagram {
+        payload: Bytes,
+    },
+    // Send time in microseconds, echoed back by a pong
+    Ping {
+        time: u64,
+    },
+    Pong {
+        time: u64,
+    },
+    // Padded to the probed size, acked by the remote with the size
+    MtuProbe {
+        size: u64,
+        padding: Payload,
+    },
+    MtuProbeAck {
+        size: u64,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -44,11 +67,61 @@ pub(crate) struct FragmentData {
     pub payload: Payload,
 }
 
+// Borrowing version of the first Packet variants, serialized the same way as a Packet.
+// Used to serialize the packets from data that is already owned somewhere else,
+// like the fragments from slices of the serialized packet, instead of copying it.
+#[derive(Serialize)]
+#[serde(rename = "Packet")]
+pub(crate) enum PacketRef<'a> {
+    Normal {
+        sequence: u16,
+        ack_data: AckData,
+        channels_packet_data: &'a [ChannelPacketData],
+    },
+    Fragment {
+        sequence: u16,
+        ack_data: AckData,
+        fragment_data: FragmentDataRef<'a>,
+    },
+}
+
+// Header of a Normal packet, deserialized from the start of the packet
+// to return its payload without deserializing the channels data.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Packet")]
+pub(crate) enum PacketHeader {
+    Normal { sequence: u16, ack_data: AckData },
+}
+
+#[derive(Serialize)]
+#[serde(rename = "FragmentData")]
+pub(crate) struct FragmentDataRef<'a> {
+    pub fragment_id: u8,
+    pub num_fragments: u8,
+    pub payload: &'a [u8],
+}
+
+impl AckData {
+    /// Inverts the ack bits inside the window, so a window with all the packets acked
+    /// is serialized with a single byte. Applying it again restores the original ack bits.
+    pub fn invert_ack_bits(self, ack_window_size: u8) -> Self {
+        let window_mask = match ack_window_size {
+            64.. => u64::MAX,
+            size => (1 << size) - 1,
+        };
+
+        Self {
+            ack: self.ack,
+            ack_bits: !self.ack_bits & window_mask,
+        }
+    }
+}
+
 impl std::fmt::Debug for AckData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Ack")
             .field("last ack", &self.ack)
-            .field("ack mask", &format!("{:031b}", &self.ack_bits))
+            .field("ack mask", &format!("{:063b}", &self.ack_bits))
             .finish()
     }
 }
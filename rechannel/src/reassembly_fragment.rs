@@ -1,7 +1,7 @@
-use crate::packet::{AckData, ChannelPacketData, FragmentData, Packet, Payload};
+use crate::error::RechannelError;
+use crate::packet::{AckData, ChannelPacketData, FragmentData, FragmentDataRef, PacketRef, Payload};
 use crate::sequence_buffer::SequenceBuffer;
-
-use bincode::Options;
+use crate::serialization::{PacketSerializer, SerializationError};
 
 use std::error::Error;
 use std::fmt;
@@ -10,11 +10,27 @@ use std::fmt;
 #[derive(Debug, Clone)]
 pub struct FragmentConfig {
     /// Packets are fragmented when size (bytes) is above.
+    /// Should be kept under the path MTU to avoid fragmentation at the IP level.
     pub fragment_above: u64,
     /// Packet is split up into fragments of this size (bytes).
     pub fragment_size: usize,
     /// Number of packet entries in the fragmentation reassembly sequence buffer.
     pub reassembly_buffer_size: usize,
+    /// Maximum number of fragments of a packet. Packets that need more fragments are not sent,
+    /// and received fragments claiming more are rejected before allocating the reassembly buffer.
+    pub max_fragments: u8,
+}
+
+/// Statistics of the fragmented packets reassembly.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyStats {
+    /// Number of incomplete packets currently in the reassembly buffer.
+    pub buffered: usize,
+    /// Number of packets completely reassembled.
+    pub completed: u64,
+    /// Number of incomplete packets evicted from the reassembly buffer by newer packets,
+    /// a high value means that the reassembly buffer size is too small.
+    pub evicted: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -32,14 +48,16 @@ pub enum FragmentError {
     InvalidTotalFragment { sequence: u16, expected: u8, got: u8 },
     /// Fragment contained an invalid id.
     InvalidFragmentId { sequence: u16, id: u8, total: u8 },
+    /// Fragment payload is bigger than the fragment size, or smaller when it's not the last fragment
+    InvalidFragmentSize { sequence: u16, id: u8, size: usize },
     /// Tried to process duplicated fragment
     AlreadyProcessed { sequence: u16, id: u8 },
     /// Fragment contained fragment count above the limit set by the configuration
-    ExceededMaxFragmentCount { sequence: u16, expected: u8, got: u8 },
+    ExceededMaxFragmentCount { sequence: u16, expected: usize, got: usize },
     /// Fragment too old to be processed
     OldSequence { sequence: u16 },
-    /// (De)serialization error
-    BincodeError(bincode::Error),
+    /// Failed to deserialize the reassembled packet
+    SerializationError { sequence: u16, error: SerializationError },
 }
 
 impl fmt::Display for FragmentError {
@@ -61,56 +79,54 @@ impl fmt::Display for FragmentError {
                     sequence, id, total
                 )
             }
+            InvalidFragmentSize { sequence, id, size } => {
+                write!(fmt, "fragment with sequence {} and id {} has invalid size {}", sequence, id, size)
+            }
             AlreadyProcessed { sequence, id } => {
                 write!(fmt, "fragment with sequence {} and id {} fragment already processed.", sequence, id)
             }
             ExceededMaxFragmentCount { sequence, expected, got } => {
                 write!(
                     fmt,
-                    "fragmentation with sequence {} exceeded maximum count, got {}, expected < {}",
+                    "fragmentation with sequence {} exceeded maximum count, got {}, expected <= {}",
                     sequence, got, expected
                 )
             }
             OldSequence { sequence } => write!(fmt, "fragment with sequence {} is too old", sequence),
-            BincodeError(ref bincode_err) => write!(fmt, "bincode error: {}", bincode_err),
+            SerializationError { sequence, ref error } => {
+                write!(
+                    fmt,
+                    "failed to deserialize reassembled packet with sequence {}: {}",
+                    sequence, error
+                )
+            }
         }
     }
 }
 
 impl Error for FragmentError {}
 
-impl From<bincode::Error> for FragmentError {
-    fn from(inner: bincode::Error) -> Self {
-        FragmentError::BincodeError(inner)
-    }
-}
-
 impl Default for FragmentConfig {
     fn default() -> Self {
         Self {
             fragment_above: 1024,
             fragment_size: 1024,
             reassembly_buffer_size: 256,
+            max_fragments: u8::MAX,
         }
     }
 }
 
 impl FragmentConfig {
-    /// Asserts that the configuration can fragment packets with the given size.
-    pub(crate) fn assert_can_fragment_packet_with_size(&self, packet_size: u64) {
-        let max_fragments = self.num_fragments(packet_size);
-        assert!(
-            max_fragments <= 256,
-            "Invalid fragmentation configuration: limit for fragments allowed is 256, got {}. \
-            Reduce the max packet size or increase the fragment size",
-            max_fragments
-        );
-    }
-
-    fn num_fragments(&self, packet_size: u64) -> u64 {
+    pub(crate) fn num_fragments(&self, packet_size: u64) -> u64 {
         let not_exact_division = u64::from(packet_size % self.fragment_size as u64 != 0);
         (packet_size / self.fragment_size as u64) + not_exact_division
     }
+
+    /// Returns the maximum number of fragments of a packet, limited by the max packet size.
+    pub(crate) fn max_fragments(&self, max_packet_size: u64) -> usize {
+        self.num_fragments(max_packet_size).min(self.max_fragments as u64) as usize
+    }
 }
 
 impl ReassemblyFragment {
@@ -129,45 +145,77 @@ impl ReassemblyFragment {
 }
 
 impl SequenceBuffer<ReassemblyFragment> {
-    pub fn handle_fragment(
+    pub fn handle_fragment<S: PacketSerializer>(
         &mut self,
         sequence: u16,
         fragment_data: FragmentData,
         max_packet_size: u64,
         config: &FragmentConfig,
+        serializer: &S,
     ) -> Result<Option<Vec<ChannelPacketData>>, FragmentError> {
+        match self.handle_fragment_raw(sequence, fragment_data, max_packet_size, config)? {
+            None => Ok(None),
+            Some(payload) => {
+                let messages: Vec<ChannelPacketData> = serializer
+                    .deserialize(&payload)
+                    .map_err(|error| FragmentError::SerializationError { sequence, error })?;
+
+                Ok(Some(messages))
+            }
+        }
+    }
+
+    /// Handles the fragment like `handle_fragment`, but returns the reassembled payload without deserializing it.
+    pub fn handle_fragment_raw(
+        &mut self,
+        sequence: u16,
+        fragment_data: FragmentData,
+        max_packet_size: u64,
+        config: &FragmentConfig,
+    ) -> Result<Option<Vec<u8>>, FragmentError> {
         let FragmentData {
             fragment_id,
             num_fragments,
             payload,
         } = fragment_data;
-        let reassembly_fragment = self
-            .get_or_insert_with(sequence, || ReassemblyFragment::new(sequence, num_fragments, config.fragment_size))
-            .ok_or(FragmentError::OldSequence { sequence })?;
-
-        let max_fragments = config.num_fragments(max_packet_size);
 
-        if reassembly_fragment.num_fragments_total as u64 > max_fragments {
+        // Validate the fragment header before allocating the reassembly buffer,
+        // and the payload size so it always fits in the buffer.
+        let max_fragments = config.max_fragments(max_packet_size);
+        if num_fragments as usize > max_fragments {
             return Err(FragmentError::ExceededMaxFragmentCount {
                 sequence,
-                expected: max_fragments as u8,
-                got: num_fragments,
+                expected: max_fragments,
+                got: num_fragments as usize,
             });
         }
 
-        if reassembly_fragment.num_fragments_total != num_fragments {
-            return Err(FragmentError::InvalidTotalFragment {
+        if fragment_id >= num_fragments {
+            return Err(FragmentError::InvalidFragmentId {
                 sequence,
-                expected: reassembly_fragment.num_fragments_total,
-                got: num_fragments,
+                id: fragment_id,
+                total: num_fragments,
             });
         }
 
-        if fragment_id >= reassembly_fragment.num_fragments_total {
-            return Err(FragmentError::InvalidFragmentId {
+        let is_last_fragment = fragment_id == num_fragments - 1;
+        if payload.len() > config.fragment_size || (!is_last_fragment && payload.len() != config.fragment_size) {
+            return Err(FragmentError::InvalidFragmentSize {
                 sequence,
                 id: fragment_id,
-                total: reassembly_fragment.num_fragments_total,
+                size: payload.len(),
+            });
+        }
+
+        let reassembly_fragment = self
+            .get_or_insert_with(sequence, || ReassemblyFragment::new(sequence, num_fragments, config.fragment_size))
+            .ok_or(FragmentError::OldSequence { sequence })?;
+
+        if reassembly_fragment.num_fragments_total != num_fragments {
+            return Err(FragmentError::InvalidTotalFragment {
+                sequence,
+                expected: reassembly_fragment.num_fragments_total,
+                got: num_fragments,
             });
         }
 
@@ -187,7 +235,7 @@ impl SequenceBuffer<ReassemblyFragment> {
         );
 
         // Resize buffer to fit the last fragment size
-        if fragment_id == num_fragments - 1 {
+        if is_last_fragment {
             let len = (reassembly_fragment.num_fragments_total - 1) as usize * config.fragment_size + payload.len();
             reassembly_fragment.buffer.resize(len, 0);
         }
@@ -197,39 +245,46 @@ impl SequenceBuffer<ReassemblyFragment> {
         if reassembly_fragment.num_fragments_received == reassembly_fragment.num_fragments_total {
             let reassembly_fragment = self.remove(sequence).expect("ReassemblyFragment always exists here");
 
-            let messages: Vec<ChannelPacketData> = bincode::options().deserialize(&reassembly_fragment.buffer)?;
-
             log::trace!("Completed the reassembly of packet {}.", reassembly_fragment.sequence);
-            return Ok(Some(messages));
+            return Ok(Some(reassembly_fragment.buffer));
         }
 
         Ok(None)
     }
 }
 
-pub(crate) fn build_fragments(
-    channels_packet_data: Vec<ChannelPacketData>,
+pub(crate) fn build_fragments<S: PacketSerializer>(
+    channels_packet_data: &[ChannelPacketData],
     sequence: u16,
     ack_data: AckData,
     config: &FragmentConfig,
-) -> Result<Vec<Payload>, bincode::Error> {
-    let payload = bincode::options().serialize(&channels_packet_data)?;
+    serializer: &S,
+) -> Result<Vec<Payload>, RechannelError> {
+    let payload = serializer.serialize(channels_packet_data)?;
     let packet_bytes = payload.len();
     let exact_division = (packet_bytes % config.fragment_size != 0) as usize;
     let num_fragments = packet_bytes / config.fragment_size + exact_division;
+    if num_fragments > config.max_fragments as usize {
+        return Err(FragmentError::ExceededMaxFragmentCount {
+            sequence,
+            expected: config.max_fragments as usize,
+            got: num_fragments,
+        }
+        .into());
+    }
 
     let mut fragments = Vec::with_capacity(num_fragments);
     for (id, chunk) in payload.chunks(config.fragment_size).enumerate() {
-        let fragment = Packet::Fragment {
+        let fragment = PacketRef::Fragment {
             sequence,
             ack_data,
-            fragment_data: FragmentData {
+            fragment_data: FragmentDataRef {
                 fragment_id: id as u8,
                 num_fragments: num_fragments as u8,
-                payload: chunk.into(),
+                payload: chunk,
             },
         };
-        let fragment = bincode::options().serialize(&fragment)?;
+        let fragment = serializer.serialize(&fragment)?;
         fragments.push(fragment);
     }
 
@@ -239,6 +294,8 @@ pub(crate) fn build_fragments(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::packet::Packet;
+    use crate::serialization::BincodeSerializer;
 
     #[test]
     fn fragment() {
@@ -251,14 +308,14 @@ mod tests {
         }];
         let sequence = 0;
 
-        let fragments = build_fragments(messages.clone(), sequence, ack_data, &config).unwrap();
+        let fragments = build_fragments(&messages, sequence, ack_data, &config, &BincodeSerializer).unwrap();
         let mut fragments_reassembly: SequenceBuffer<ReassemblyFragment> = SequenceBuffer::with_capacity(256);
         assert_eq!(3, fragments.len());
 
         let fragments: Vec<FragmentData> = fragments
             .iter()
             .map(|payload| {
-                let fragment: Packet = bincode::options().deserialize(payload).unwrap();
+                let fragment: Packet = BincodeSerializer.deserialize(payload).unwrap();
                 match fragment {
                     Packet::Fragment { fragment_data, .. } => fragment_data,
                     _ => panic!(),
@@ -266,23 +323,140 @@ mod tests {
             })
             .collect();
 
-        let result = fragments_reassembly.handle_fragment(sequence, fragments[0].clone(), 250_000, &config);
+        let result = fragments_reassembly.handle_fragment(sequence, fragments[0].clone(), 250_000, &config, &BincodeSerializer);
         match result {
             Ok(payloads) => assert!(payloads.is_none()),
             _ => unreachable!(),
         }
 
-        let result = fragments_reassembly.handle_fragment(sequence, fragments[1].clone(), 250_000, &config);
+        let result = fragments_reassembly.handle_fragment(sequence, fragments[1].clone(), 250_000, &config, &BincodeSerializer);
         match result {
             Ok(payloads) => assert!(payloads.is_none()),
             _ => unreachable!(),
         }
 
-        let result = fragments_reassembly.handle_fragment(sequence, fragments[2].clone(), 250_000, &config);
+        let result = fragments_reassembly.handle_fragment(sequence, fragments[2].clone(), 250_000, &config, &BincodeSerializer);
         let result = result.unwrap().unwrap();
 
         assert_eq!(messages.len(), result.len());
 
         assert_eq!(messages[0], result[0]);
     }
+
+    #[test]
+    fn fragment_error_context() {
+        let config = FragmentConfig::default();
+        let mut fragments_reassembly: SequenceBuffer<ReassemblyFragment> = SequenceBuffer::with_capacity(256);
+        let sequence = 7;
+        let fragment_data = FragmentData {
+            fragment_id: 3,
+            num_fragments: 2,
+            payload: vec![0u8; 10],
+        };
+
+        let result = fragments_reassembly.handle_fragment(sequence, fragment_data, 250_000, &config, &BincodeSerializer);
+        assert!(matches!(
+            result,
+            Err(FragmentError::InvalidFragmentId {
+                sequence: 7,
+                id: 3,
+                total: 2
+            })
+        ));
+
+        // Fragments that can't be deserialized once reassembled
+        let fragment_data = FragmentData {
+            fragment_id: 0,
+            num_fragments: 2,
+            payload: vec![255u8; config.fragment_size],
+        };
+        let result = fragments_reassembly.handle_fragment(sequence, fragment_data.clone(), 250_000, &config, &BincodeSerializer);
+        assert!(matches!(result, Ok(None)));
+
+        let fragment_data = FragmentData {
+            fragment_id: 1,
+            ..fragment_data
+        };
+        let result = fragments_reassembly.handle_fragment(sequence, fragment_data, 250_000, &config, &BincodeSerializer);
+        assert!(matches!(result, Err(FragmentError::SerializationError { sequence: 7, .. })));
+    }
+
+    #[test]
+    fn invalid_fragment_size() {
+        let config = FragmentConfig::default();
+        let mut fragments_reassembly: SequenceBuffer<ReassemblyFragment> = SequenceBuffer::with_capacity(256);
+
+        // Payload that would not fit in the reassembly buffer
+        let fragment_data = FragmentData {
+            fragment_id: 0,
+            num_fragments: 2,
+            payload: vec![0u8; config.fragment_size * 2],
+        };
+        let result = fragments_reassembly.handle_fragment_raw(0, fragment_data, 250_000, &config);
+        assert!(matches!(result, Err(FragmentError::InvalidFragmentSize { sequence: 0, id: 0, .. })));
+
+        // Only the last fragment can be smaller than the fragment size
+        let fragment_data = FragmentData {
+            fragment_id: 0,
+            num_fragments: 2,
+            payload: vec![0u8; 10],
+        };
+        let result = fragments_reassembly.handle_fragment_raw(0, fragment_data, 250_000, &config);
+        assert!(matches!(
+            result,
+            Err(FragmentError::InvalidFragmentSize {
+                sequence: 0,
+                id: 0,
+                size: 10
+            })
+        ));
+
+        let fragment_data = FragmentData {
+            fragment_id: 1,
+            num_fragments: 2,
+            payload: vec![0u8; 10],
+        };
+        let result = fragments_reassembly.handle_fragment_raw(0, fragment_data, 250_000, &config);
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[test]
+    fn max_fragments() {
+        let config = FragmentConfig {
+            max_fragments: 2,
+            ..Default::default()
+        };
+        let mut fragments_reassembly: SequenceBuffer<ReassemblyFragment> = SequenceBuffer::with_capacity(256);
+
+        // Fragment claiming more fragments than allowed is rejected before allocating the buffer
+        let fragment_data = FragmentData {
+            fragment_id: 0,
+            num_fragments: u8::MAX,
+            payload: vec![0u8; config.fragment_size],
+        };
+        let result = fragments_reassembly.handle_fragment_raw(0, fragment_data, 250_000, &config);
+        assert!(matches!(
+            result,
+            Err(FragmentError::ExceededMaxFragmentCount {
+                sequence: 0,
+                expected: 2,
+                got: 255
+            })
+        ));
+        assert!(!fragments_reassembly.exists(0));
+
+        let ack_data = AckData { ack: 0, ack_bits: 0 };
+        let messages = vec![ChannelPacketData {
+            channel_id: 0,
+            messages: vec![vec![0u8; 3000]],
+        }];
+        let result = build_fragments(&messages, 0, ack_data, &config, &BincodeSerializer);
+        assert!(matches!(
+            result,
+            Err(RechannelError::FragmentError(FragmentError::ExceededMaxFragmentCount {
+                got: 3,
+                ..
+            }))
+        ));
+    }
 }
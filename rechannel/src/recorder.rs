@@ -0,0 +1,197 @@
+use crate::error::RechannelError;
+use crate::packet::Payload;
+use crate::remote_connection::RemoteConnection;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// Direction of a recorded packet, relative to the recorded connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    Sent,
+    Received,
+}
+
+/// A packet recorded by the [PacketRecorder].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketRecord {
+    pub timestamp: Duration,
+    pub direction: PacketDirection,
+    pub payload: Payload,
+}
+
+/// Records the packets sent and received by a connection with their timestamp,
+/// so they can be replayed later with the [PacketReplayer].
+/// Sent packets are the ones returned by `get_packets_to_send`,
+/// and received packets are the ones passed to `process_packet`.
+///
+/// Each record is written as the timestamp in microseconds (u64), the direction (u8),
+/// the payload length (u32) and the payload, with integers in little endian.
+#[derive(Debug)]
+pub struct PacketRecorder<W: Write> {
+    writer: W,
+}
+
+/// Reads the packets recorded by a [PacketRecorder].
+#[derive(Debug)]
+pub struct PacketReplayer<R: Read> {
+    reader: R,
+    current_time: Duration,
+}
+
+/// Possible errors when replaying packets.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// An error occurred reading the records
+    IoError(io::Error),
+    /// An error occurred processing a received packet
+    RechannelError(RechannelError),
+}
+
+impl std::error::Error for ReplayError {}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ReplayError::*;
+
+        match *self {
+            IoError(ref io_err) => write!(fmt, "{}", io_err),
+            RechannelError(ref rechannel_err) => write!(fmt, "{}", rechannel_err),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(inner: io::Error) -> Self {
+        ReplayError::IoError(inner)
+    }
+}
+
+impl From<RechannelError> for ReplayError {
+    fn from(inner: RechannelError) -> Self {
+        ReplayError::RechannelError(inner)
+    }
+}
+
+impl<W: Write> PacketRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn record_sent(&mut self, timestamp: Duration, packet: &[u8]) -> Result<(), io::Error> {
+        self.record(timestamp, PacketDirection::Sent, packet)
+    }
+
+    pub fn record_received(&mut self, timestamp: Duration, packet: &[u8]) -> Result<(), io::Error> {
+        self.record(timestamp, PacketDirection::Received, packet)
+    }
+
+    pub fn record(&mut self, timestamp: Duration, direction: PacketDirection, packet: &[u8]) -> Result<(), io::Error> {
+        let direction = match direction {
+            PacketDirection::Sent => 0u8,
+            PacketDirection::Received => 1u8,
+        };
+
+        self.writer.write_all(&(timestamp.as_micros() as u64).to_le_bytes())?;
+        self.writer.write_all(&[direction])?;
+        self.writer.write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.writer.write_all(packet)
+    }
+
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<R: Read> PacketReplayer<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            current_time: Duration::ZERO,
+        }
+    }
+
+    /// Returns the next record, or None when all the records were read.
+    pub fn next_record(&mut self) -> Result<Option<PacketRecord>, io::Error> {
+        let mut timestamp = [0u8; 8];
+        match self.reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let mut direction = [0u8; 1];
+        self.reader.read_exact(&mut direction)?;
+        let direction = match direction[0] {
+            0 => PacketDirection::Sent,
+            1 => PacketDirection::Received,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid packet direction")),
+        };
+
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut payload = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut payload)?;
+
+        Ok(Some(PacketRecord {
+            timestamp: Duration::from_micros(u64::from_le_bytes(timestamp)),
+            direction,
+            payload,
+        }))
+    }
+
+    /// Feeds all the received packets to the connection in the recorded order,
+    /// advancing its time to the timestamp of each packet.
+    /// The connection should have been created with the same start time as the recorded one.
+    pub fn replay(&mut self, connection: &mut RemoteConnection) -> Result<(), ReplayError> {
+        while let Some(record) = self.next_record()? {
+            if record.direction != PacketDirection::Received {
+                continue;
+            }
+
+            if record.timestamp > self.current_time {
+                connection.advance_time(record.timestamp - self.current_time);
+                self.current_time = record.timestamp;
+            }
+            connection.process_packet(&record.payload)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote_connection::ConnectionConfig;
+
+    #[test]
+    fn record_and_replay() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut recorder = PacketRecorder::new(vec![]);
+
+        connection.send_message(0, vec![1, 2, 3]);
+        connection.send_message(0, vec![4, 5, 6]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            recorder.record_received(Duration::from_millis(10), &packet).unwrap();
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        recorder.record_sent(Duration::from_millis(20), &[0u8; 4]).unwrap();
+
+        let records = recorder.into_inner();
+        let mut replayer = PacketReplayer::new(records.as_slice());
+        let mut replayed_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        replayer.replay(&mut replayed_connection).unwrap();
+
+        for expected in [vec![1, 2, 3], vec![4, 5, 6]] {
+            assert_eq!(remote_connection.receive_message(0).unwrap(), expected);
+            assert_eq!(replayed_connection.receive_message(0).unwrap(), expected);
+        }
+    }
+}
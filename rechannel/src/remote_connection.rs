@@ -1,22 +1,162 @@
-use crate::channel::{ChannelConfig, DefaultChannel, ReceiveChannel, SendChannel};
-use crate::error::{DisconnectionReason, RechannelError};
-use crate::packet::{Packet, Payload};
-
-use crate::reassembly_fragment::{build_fragments, FragmentConfig, ReassemblyFragment};
-use crate::sequence_buffer::SequenceBuffer;
+use crate::channel::{ChannelConfig, ChunkProgress, DefaultChannel, ReceiveChannel, SendChannel};
+use crate::error::{ConfigError, DisconnectionReason, RechannelError};
+use crate::observer::{ConnectionObserver, DroppedPacket, LogObserver};
+use crate::packet::{AckData, ChannelPacketData, Packet, PacketHeader, PacketRef, Payload};
+
+use crate::reassembly_fragment::{build_fragments, FragmentConfig, FragmentError, ReassemblyFragment, ReassemblyStats};
+use crate::sequence_buffer::{sequence_greater_than, SequenceBuffer};
+use crate::serialization::{BincodeSerializer, PacketSerializer, SerializationError};
 use crate::timer::Timer;
 
-use bincode::Options;
 use bytes::Bytes;
 use log::error;
+use serde::Serialize;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Number of times the disconnect packet is sent, so it can survive packet loss.
+const NUM_DISCONNECT_PACKETS_TO_SEND: usize = 5;
+
+/// Maximum number of received datagrams waiting to be read,
+/// the oldest datagram is dropped when a new one arrives above it.
+const MAX_RECEIVED_DATAGRAMS: usize = 256;
+
 #[derive(Debug, Clone)]
 struct SentPacket {
     time: Duration,
     ack: bool,
+    nack: bool,
+    /// Serialized size (bytes) of each channel data in the packet.
+    channels_size: Vec<(u8, u64)>,
+}
+
+#[derive(Debug, Clone)]
+struct ReceivedPacket {
+    time: Duration,
+    /// Serialized size (bytes) of each channel data in the packet.
+    channels_size: Vec<(u8, u64)>,
+}
+
+/// Application payload of a received packet.
+enum ReceivedPayload {
+    Channels(Vec<ChannelPacketData>),
+    Raw(Vec<u8>),
+}
+
+/// Identifies a message sent in a reliable channel, used to confirm its delivery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageId {
+    pub channel_id: u8,
+    pub id: u16,
+}
+
+/// Bandwidth used by a single channel.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChannelBandwidth {
+    /// Sent kilobits per second.
+    pub sent_kbps: f32,
+    /// Received kilobits per second.
+    pub received_kbps: f32,
+}
+
+/// Lifetime totals of the connection, unlike the bandwidth and packet loss
+/// they are not measured over a window of packets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionTotals {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub packets_acked: u64,
+}
+
+/// Number of packets received of each kind, duplicated packets are included.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PacketTypeStats {
+    /// Packets with channels messages.
+    pub normal: u64,
+    /// Fragments of packets with channels messages.
+    pub fragment: u64,
+    pub heartbeat: u64,
+    pub disconnect: u64,
+    pub datagram: u64,
+    /// Pings and pongs.
+    pub ping: u64,
+    /// MTU probes and their acks.
+    pub mtu_probe: u64,
+}
+
+/// Statistics of how the messages are coalesced in the sent packets.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CoalesceStats {
+    /// Number of sent packets with messages.
+    pub packets: u64,
+    /// Average number of messages in each packet.
+    pub messages_per_packet: f32,
+    /// Average size of the packets relative to the size above which they are fragmented,
+    /// the fragment threshold or the discovered MTU, between 0 and 1.
+    /// A low value with many packets means that the messages could be sent less often.
+    pub fill_ratio: f32,
+}
+
+/// Network conditions detected by the congestion control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionState {
+    Good,
+    Bad,
+}
+
+/// Configuration for the congestion control, it switches the send bandwidth between a high
+/// and a low limit based on the packet loss and RTT of the connection.
+#[derive(Debug, Clone)]
+pub struct CongestionConfig {
+    /// Send bandwidth used when the network conditions are good.
+    pub good_bandwidth_kbps: f32,
+    /// Send bandwidth used when the network conditions are bad.
+    pub bad_bandwidth_kbps: f32,
+    /// Packet loss above this value is considered a bad condition.
+    /// Values between 0.0 and 1.0.
+    pub max_packet_loss: f32,
+    /// RTT above this value is considered a bad condition.
+    pub max_rtt: Duration,
+    /// Time that the network conditions need to be good before switching back from the bad state.
+    pub recovery_time: Duration,
+}
+
+/// Configuration for the path MTU discovery. Probes padded to increasing sizes are sent,
+/// and the largest acked size is the discovered MTU. The sizes are searched with a binary
+/// search between the min and max size, the max size is limited by the max packet size.
+#[derive(Debug, Clone)]
+pub struct MtuDiscoveryConfig {
+    /// Size of the smallest probe, assumed to be deliverable on any path.
+    /// The fragments are sent with the configured fragment size, so it must fit in this size.
+    pub min_size: u64,
+    /// Size of the biggest probe.
+    pub max_size: u64,
+    /// Time to wait for the ack of a probe before considering the size too big.
+    pub probe_timeout: Duration,
+    /// The search stops when the range of possible MTUs is smaller than this (bytes).
+    pub precision: u64,
+}
+
+// Aproximated size of a probe without the padding
+const MTU_PROBE_HEADER_SIZE: u64 = 7;
+
+// Aproximated header size for the packet
+const PACKET_HEADER_SIZE: u64 = 20;
+
+// Serialized size of each channel data in a packet, by channel id
+type ChannelsSize = Vec<(u8, u64)>;
+
+#[derive(Debug, Clone)]
+struct MtuProbe {
+    // Largest size acked and smallest size lost
+    low: u64,
+    high: u64,
+    // Size and send time of the probe waiting for an ack
+    in_flight: Option<(u64, Duration)>,
 }
 
 #[derive(Debug)]
@@ -33,31 +173,151 @@ pub struct ConnectionConfig {
     pub rtt_smoothing_factor: f32,
     pub packet_loss_smoothing_factor: f32,
     pub heartbeat_time: Duration,
+    /// Number of previous packets acked in each sent packet, max 64.
+    /// Values above 32 are not compatible with connections using older versions.
+    pub ack_window_size: u8,
+    /// Sequence of the first packet sent. Starting close to [u16::MAX] exercises the sequence
+    /// wraparound without sending thousands of packets, useful for tests and reproducing issues.
+    pub initial_sequence: u16,
+    /// When set, a heartbeat is sent immediately once this number of received packets
+    /// are waiting to be acked, instead of waiting for the heartbeat time.
+    pub max_pending_acks: Option<usize>,
+    /// When set, limits the bandwidth used to send packets. Channel messages above the limit
+    /// are kept in the channels until there is bandwidth available.
+    pub max_send_bandwidth_kbps: Option<f32>,
+    /// When set, the ack bits are sent inverted, saving a few bytes per packet when all
+    /// the packets in the ack window were received. Both endpoints must use the same value
+    /// and the same ack window size.
+    pub compact_acks: bool,
+    /// When set, a sent packet missing from the ack bits of a packet acked this many sequences
+    /// later is considered lost, and the reliable channels resend its messages immediately
+    /// instead of waiting for the resend time. The loss is inferred from the ack bits already
+    /// sent, so it works with any remote, but both endpoints must use the same ack window size.
+    pub nack_threshold: Option<u8>,
+    /// When set, the send bandwidth is adjusted based on the network conditions.
+    /// The max send bandwidth, if set, still limits the bandwidth.
+    pub congestion_control: Option<CongestionConfig>,
+    /// When set, a ping is sent at this interval and the remote answers it with a pong,
+    /// so the RTT is measured even when no messages are being sent.
+    /// Connections using older versions do not understand ping packets.
+    pub ping_interval: Option<Duration>,
+    /// When set, the path MTU is discovered at the start of the connection, and packets above
+    /// the discovered MTU are fragmented. The fragments with the packet header must fit
+    /// in the min size of the discovery.
+    /// Connections using older versions do not understand MTU probe packets.
+    pub mtu_discovery: Option<MtuDiscoveryConfig>,
+    /// When set, the pending channel data is coalesced into packets up to this size (bytes),
+    /// instead of a single packet that is fragmented above the fragment threshold.
+    /// Usually set below the path MTU, like 1200 bytes, the packets are also kept under the
+    /// fragment threshold. Messages that don't fit are sent alone, fragmented if needed.
+    /// The data sent at once is still limited by the max packet size.
+    pub coalesce_mtu: Option<u64>,
     pub fragment_config: FragmentConfig,
     pub send_channels_config: Vec<ChannelConfig>,
     pub receive_channels_config: Vec<ChannelConfig>,
 }
 
+/// Summary of a packet processed by the connection.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProcessSummary {
+    /// Number of messages passed to each channel, only the channels with messages are included.
+    /// Reliable channels discard the messages they already received.
+    pub messages: Vec<(u8, usize)>,
+    /// Size of the processed packet.
+    pub bytes: usize,
+    /// Number of sent packets acked by the processed packet.
+    pub acks: usize,
+    /// Whether the processed packet was a heartbeat.
+    pub heartbeat: bool,
+}
+
+impl ProcessSummary {
+    /// Returns the total number of messages received.
+    pub fn num_messages(&self) -> usize {
+        self.messages.iter().map(|(_, num_messages)| num_messages).sum()
+    }
+}
+
+/// Builder for a [ConnectionConfig], fields not set use their default value.
+/// The configuration is validated when built.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionConfigBuilder {
+    config: ConnectionConfig,
+}
+
 #[derive(Debug)]
-pub struct RemoteConnection {
+pub struct RemoteConnection<S: PacketSerializer = BincodeSerializer> {
     state: ConnectionState,
     sequence: u16,
     send_channels: HashMap<u8, Box<dyn SendChannel + Send + Sync + 'static>>,
     receive_channels: HashMap<u8, Box<dyn ReceiveChannel + Send + Sync + 'static>>,
+    removed_channels: HashSet<u8>,
     heartbeat_timer: Timer,
+    last_send_time: Duration,
+    ping_timer: Timer,
+    // Time of the last ping received, sent back in the next pong
+    pong_to_send: Option<u64>,
+    // Send time of the outstanding ping, only the pong echoing it is accepted
+    ping_sent_time: Option<u64>,
+    last_ping_rtt: Option<Duration>,
+    mtu_probe: Option<MtuProbe>,
+    // Size of the last probe received, acked in the next packets sent
+    mtu_probe_ack_to_send: Option<u64>,
+    discovered_mtu: Option<u64>,
     config: ConnectionConfig,
     reassembly_buffer: SequenceBuffer<ReassemblyFragment>,
     sent_buffer: SequenceBuffer<SentPacket>,
-    received_buffer: SequenceBuffer<()>,
+    received_buffer: SequenceBuffer<ReceivedPacket>,
     current_time: Duration,
     rtt: f32,
+    rtt_variance: f32,
     packet_loss: f32,
-    acks: Vec<u16>,
+    /// Acked sequences with the channels that had data in the packet,
+    /// channels are only notified of the acks for their own packets.
+    acks: Vec<(u16, u8)>,
+    /// Sequences considered lost with the channels that had data in the packet.
+    nacks: Vec<(u16, u8)>,
+    pending_acks: usize,
+    duplicate_fragments: u64,
+    duplicate_packets: u64,
+    unknown_channel_messages: u64,
+    dropped_datagrams: u64,
+    reassembled_packets: u64,
+    evicted_reassembly_packets: u64,
+    coalesced_packets: u64,
+    coalesced_messages: u64,
+    coalesced_bytes: u64,
+    totals: ConnectionTotals,
+    packet_type_stats: PacketTypeStats,
+    datagrams_to_send: Vec<Bytes>,
+    // Bytes available to send when the bandwidth is limited
+    send_budget: f32,
+    congestion_state: CongestionState,
+    // Reset while the network conditions are bad, the congestion state recovers when it finishes
+    congestion_recovery_timer: Timer,
+    received_datagrams: VecDeque<Payload>,
+    delivered_messages: Vec<MessageId>,
+    expired_messages: Vec<MessageId>,
+    serializer: S,
+    // Reused to serialize the packets to send
+    send_buffer: Vec<u8>,
+    observer: Arc<dyn ConnectionObserver>,
 }
 
 impl SentPacket {
-    fn new(time: Duration) -> Self {
-        Self { time, ack: false }
+    fn new(time: Duration, channels_size: Vec<(u8, u64)>) -> Self {
+        Self {
+            time,
+            ack: false,
+            nack: false,
+            channels_size,
+        }
+    }
+}
+
+impl ReceivedPacket {
+    fn new(time: Duration, channels_size: Vec<(u8, u64)>) -> Self {
+        Self { time, channels_size }
     }
 }
 
@@ -70,6 +330,16 @@ impl Default for ConnectionConfig {
             rtt_smoothing_factor: 0.01,
             packet_loss_smoothing_factor: 0.1,
             heartbeat_time: Duration::from_millis(100),
+            ack_window_size: 32,
+            initial_sequence: 0,
+            max_pending_acks: None,
+            max_send_bandwidth_kbps: None,
+            compact_acks: false,
+            nack_threshold: None,
+            congestion_control: None,
+            ping_interval: None,
+            mtu_discovery: None,
+            coalesce_mtu: None,
             fragment_config: FragmentConfig::default(),
             send_channels_config: DefaultChannel::config(),
             receive_channels_config: DefaultChannel::config(),
@@ -77,11 +347,286 @@ impl Default for ConnectionConfig {
     }
 }
 
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            good_bandwidth_kbps: 1024.,
+            bad_bandwidth_kbps: 256.,
+            max_packet_loss: 0.05,
+            max_rtt: Duration::from_millis(250),
+            recovery_time: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Default for MtuDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 508,
+            max_size: 1472,
+            probe_timeout: Duration::from_secs(1),
+            precision: 32,
+        }
+    }
+}
+
+impl MtuProbe {
+    fn new(config: &ConnectionConfig) -> Option<Self> {
+        config.mtu_discovery.as_ref().map(|mtu_config| Self {
+            low: mtu_config.min_size,
+            high: mtu_config.max_size.min(config.max_packet_size) + 1,
+            in_flight: None,
+        })
+    }
+}
+
+impl ConnectionConfigBuilder {
+    pub fn max_packet_size(mut self, max_packet_size: u64) -> Self {
+        self.config.max_packet_size = max_packet_size;
+        self
+    }
+
+    pub fn sent_packets_buffer_size(mut self, size: usize) -> Self {
+        self.config.sent_packets_buffer_size = size;
+        self
+    }
+
+    pub fn received_packets_buffer_size(mut self, size: usize) -> Self {
+        self.config.received_packets_buffer_size = size;
+        self
+    }
+
+    pub fn rtt_smoothing_factor(mut self, factor: f32) -> Self {
+        self.config.rtt_smoothing_factor = factor;
+        self
+    }
+
+    pub fn packet_loss_smoothing_factor(mut self, factor: f32) -> Self {
+        self.config.packet_loss_smoothing_factor = factor;
+        self
+    }
+
+    pub fn heartbeat_time(mut self, heartbeat_time: Duration) -> Self {
+        self.config.heartbeat_time = heartbeat_time;
+        self
+    }
+
+    pub fn ack_window_size(mut self, ack_window_size: u8) -> Self {
+        self.config.ack_window_size = ack_window_size;
+        self
+    }
+
+    pub fn max_pending_acks(mut self, max_pending_acks: usize) -> Self {
+        self.config.max_pending_acks = Some(max_pending_acks);
+        self
+    }
+
+    pub fn max_send_bandwidth_kbps(mut self, max_send_bandwidth_kbps: f32) -> Self {
+        self.config.max_send_bandwidth_kbps = Some(max_send_bandwidth_kbps);
+        self
+    }
+
+    pub fn compact_acks(mut self, compact_acks: bool) -> Self {
+        self.config.compact_acks = compact_acks;
+        self
+    }
+
+    pub fn initial_sequence(mut self, initial_sequence: u16) -> Self {
+        self.config.initial_sequence = initial_sequence;
+        self
+    }
+
+    pub fn nack_threshold(mut self, nack_threshold: u8) -> Self {
+        self.config.nack_threshold = Some(nack_threshold);
+        self
+    }
+
+    pub fn congestion_control(mut self, congestion_control: CongestionConfig) -> Self {
+        self.config.congestion_control = Some(congestion_control);
+        self
+    }
+
+    pub fn ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.config.ping_interval = Some(ping_interval);
+        self
+    }
+
+    pub fn mtu_discovery(mut self, mtu_discovery: MtuDiscoveryConfig) -> Self {
+        self.config.mtu_discovery = Some(mtu_discovery);
+        self
+    }
+
+    pub fn coalesce_mtu(mut self, coalesce_mtu: u64) -> Self {
+        self.config.coalesce_mtu = Some(coalesce_mtu);
+        self
+    }
+
+    pub fn fragment_config(mut self, fragment_config: FragmentConfig) -> Self {
+        self.config.fragment_config = fragment_config;
+        self
+    }
+
+    pub fn fragment_above(mut self, fragment_above: u64) -> Self {
+        self.config.fragment_config.fragment_above = fragment_above;
+        self
+    }
+
+    pub fn fragment_size(mut self, fragment_size: usize) -> Self {
+        self.config.fragment_config.fragment_size = fragment_size;
+        self
+    }
+
+    pub fn reassembly_buffer_size(mut self, size: usize) -> Self {
+        self.config.fragment_config.reassembly_buffer_size = size;
+        self
+    }
+
+    pub fn max_fragments(mut self, max_fragments: u8) -> Self {
+        self.config.fragment_config.max_fragments = max_fragments;
+        self
+    }
+
+    pub fn send_channels_config(mut self, channels_config: Vec<ChannelConfig>) -> Self {
+        self.config.send_channels_config = channels_config;
+        self
+    }
+
+    pub fn receive_channels_config(mut self, channels_config: Vec<ChannelConfig>) -> Self {
+        self.config.receive_channels_config = channels_config;
+        self
+    }
+
+    /// Uses the same channels configuration to send and receive messages.
+    pub fn channels_config(mut self, channels_config: Vec<ChannelConfig>) -> Self {
+        self.config.send_channels_config = channels_config.clone();
+        self.config.receive_channels_config = channels_config;
+        self
+    }
+
+    pub fn build(self) -> Result<ConnectionConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+impl ConnectionConfig {
+    pub fn builder() -> ConnectionConfigBuilder {
+        ConnectionConfigBuilder::default()
+    }
+
+    /// Returns the send bandwidth limit for the congestion state.
+    fn send_bandwidth_kbps(&self, congestion_state: CongestionState) -> Option<f32> {
+        let congestion_bandwidth_kbps = self.congestion_control.as_ref().map(|congestion| match congestion_state {
+            CongestionState::Good => congestion.good_bandwidth_kbps,
+            CongestionState::Bad => congestion.bad_bandwidth_kbps,
+        });
+
+        match (self.max_send_bandwidth_kbps, congestion_bandwidth_kbps) {
+            (Some(max), Some(congestion)) => Some(max.min(congestion)),
+            (max, congestion) => max.or(congestion),
+        }
+    }
+
+    /// Verifies that the configuration is valid, the connection panics when created with an invalid one.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.fragment_config.fragment_size == 0 {
+            return Err(ConfigError::ZeroFragmentSize);
+        }
+
+        let num_fragments = self.fragment_config.num_fragments(self.max_packet_size);
+        if num_fragments > self.fragment_config.max_fragments as u64 {
+            return Err(ConfigError::TooManyFragments {
+                max_packet_size: self.max_packet_size,
+                num_fragments,
+                max_fragments: self.fragment_config.max_fragments,
+            });
+        }
+
+        if self.fragment_config.fragment_above > self.max_packet_size {
+            return Err(ConfigError::FragmentAboveMaxPacketSize {
+                fragment_above: self.fragment_config.fragment_above,
+                max_packet_size: self.max_packet_size,
+            });
+        }
+
+        // The fragment size is not lowered with the discovered MTU, the remote expects the configured size
+        if let Some(mtu_discovery) = &self.mtu_discovery {
+            if self.fragment_config.fragment_size as u64 + PACKET_HEADER_SIZE > mtu_discovery.min_size {
+                return Err(ConfigError::FragmentSizeAboveMtu {
+                    fragment_size: self.fragment_config.fragment_size,
+                    min_mtu: mtu_discovery.min_size,
+                });
+            }
+        }
+
+        validate_smoothing_factor("rtt smoothing factor", self.rtt_smoothing_factor)?;
+        validate_smoothing_factor("packet loss smoothing factor", self.packet_loss_smoothing_factor)?;
+
+        let mut buffers = vec![
+            ("sent packets buffer", self.sent_packets_buffer_size),
+            ("received packets buffer", self.received_packets_buffer_size),
+            ("reassembly buffer", self.fragment_config.reassembly_buffer_size),
+        ];
+        for channel_config in self.send_channels_config.iter().chain(self.receive_channels_config.iter()) {
+            buffers.extend(channel_config.sequence_buffers_size());
+        }
+        for (buffer, size) in buffers {
+            if !size.is_power_of_two() {
+                return Err(ConfigError::BufferSizeNotPowerOfTwo { buffer, size });
+            }
+        }
+
+        if self.ack_window_size > 64 {
+            return Err(ConfigError::AckWindowTooLarge(self.ack_window_size));
+        }
+
+        for channels_config in [&self.send_channels_config, &self.receive_channels_config] {
+            let mut channels_id = HashSet::new();
+            for channel_config in channels_config.iter() {
+                if !channels_id.insert(channel_config.channel_id()) {
+                    return Err(ConfigError::DuplicatedChannelId(channel_config.channel_id()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl RemoteConnection {
+    /// Creates a connection, panics if the config is invalid.
     pub fn new(current_time: Duration, config: ConnectionConfig) -> Self {
-        config.fragment_config.assert_can_fragment_packet_with_size(config.max_packet_size);
+        Self::with_serializer(current_time, config, BincodeSerializer)
+    }
+
+    /// Creates a connection, returns an error if the config is invalid.
+    pub fn try_new(current_time: Duration, config: ConnectionConfig) -> Result<Self, ConfigError> {
+        Self::try_with_serializer(current_time, config, BincodeSerializer)
+    }
+}
+
+impl<S: PacketSerializer> RemoteConnection<S> {
+    /// Creates a connection that uses the given serializer for the packets, panics if the config is invalid.
+    /// Both endpoints of the connection must use the same serialization.
+    pub fn with_serializer(current_time: Duration, config: ConnectionConfig, serializer: S) -> Self {
+        match Self::try_with_serializer(current_time, config, serializer) {
+            Ok(connection) => connection,
+            Err(e) => panic!("Invalid connection config: {}", e),
+        }
+    }
+
+    /// Creates a connection that uses the given serializer for the packets, returns an error if the config is invalid.
+    /// Both endpoints of the connection must use the same serialization.
+    pub fn try_with_serializer(current_time: Duration, config: ConnectionConfig, serializer: S) -> Result<Self, ConfigError> {
+        config.validate()?;
 
         let heartbeat_timer = Timer::new(current_time, config.heartbeat_time);
+        let ping_timer = Timer::new(current_time, config.ping_interval.unwrap_or_default());
+        let send_budget = config.max_packet_size as f32;
+        let mtu_probe = MtuProbe::new(&config);
+        let send_buffer = Vec::with_capacity(config.max_packet_size as usize);
+        let recovery_time = config.congestion_control.as_ref().map(|c| c.recovery_time).unwrap_or_default();
+        let congestion_recovery_timer = Timer::new(current_time, recovery_time);
         let reassembly_buffer = SequenceBuffer::with_capacity(config.fragment_config.reassembly_buffer_size);
         let sent_buffer = SequenceBuffer::with_capacity(config.sent_packets_buffer_size);
         let received_buffer = SequenceBuffer::with_capacity(config.received_packets_buffer_size);
@@ -90,43 +635,239 @@ impl RemoteConnection {
         for channel_config in config.send_channels_config.iter() {
             let (send_channel, _) = channel_config.new_channels();
             let channel_id = channel_config.channel_id();
-            let old_channel = send_channels.insert(channel_id, send_channel);
-            assert!(old_channel.is_none(), "already exists send channel with id {}", channel_id);
+            send_channels.insert(channel_id, send_channel);
         }
 
         let mut receive_channels = HashMap::new();
         for channel_config in config.receive_channels_config.iter() {
             let (_, receive_channel) = channel_config.new_channels();
             let channel_id = channel_config.channel_id();
-            let old_channel = receive_channels.insert(channel_id, receive_channel);
-            assert!(old_channel.is_none(), "already exists receive channel with id {}", channel_id);
+            receive_channels.insert(channel_id, receive_channel);
         }
 
-        Self {
+        Ok(Self {
             state: ConnectionState::Connected,
             send_channels,
             receive_channels,
+            removed_channels: HashSet::new(),
             heartbeat_timer,
-            sequence: 0,
+            last_send_time: current_time,
+            ping_timer,
+            pong_to_send: None,
+            ping_sent_time: None,
+            last_ping_rtt: None,
+            mtu_probe,
+            mtu_probe_ack_to_send: None,
+            discovered_mtu: None,
+            sequence: config.initial_sequence,
             reassembly_buffer,
             sent_buffer,
             received_buffer,
             current_time,
             config,
             rtt: 0.0,
+            rtt_variance: 0.0,
             packet_loss: 0.0,
             acks: vec![],
-        }
+            nacks: vec![],
+            pending_acks: 0,
+            duplicate_fragments: 0,
+            duplicate_packets: 0,
+            unknown_channel_messages: 0,
+            dropped_datagrams: 0,
+            reassembled_packets: 0,
+            evicted_reassembly_packets: 0,
+            coalesced_packets: 0,
+            coalesced_messages: 0,
+            coalesced_bytes: 0,
+            totals: ConnectionTotals::default(),
+            packet_type_stats: PacketTypeStats::default(),
+            datagrams_to_send: vec![],
+            send_budget,
+            congestion_state: CongestionState::Good,
+            congestion_recovery_timer,
+            received_datagrams: VecDeque::new(),
+            delivered_messages: vec![],
+            expired_messages: vec![],
+            serializer,
+            send_buffer,
+            observer: Arc::new(LogObserver),
+        })
+    }
+
+    /// Sets the observer notified of the connection events, replacing the default [LogObserver].
+    pub fn set_observer<O: ConnectionObserver + 'static>(&mut self, observer: O) {
+        self.observer = Arc::new(observer);
+    }
+
+    /// Returns the path MTU discovered with the probes, only available when the MTU discovery
+    /// is set in the connection config and the search is finished. The MTU is the size of the
+    /// packets returned by [get_packets_to_send][Self::get_packets_to_send].
+    pub fn discovered_mtu(&self) -> Option<u64> {
+        self.discovered_mtu
     }
 
     pub fn rtt(&self) -> f32 {
         self.rtt
     }
 
+    /// Changes the smoothing factor of the RTT, for example to react faster to the RTT
+    /// changes while the connection warms up. The factor must be between 0.0 and 1.0.
+    pub fn set_rtt_smoothing_factor(&mut self, factor: f32) -> Result<(), ConfigError> {
+        validate_smoothing_factor("rtt smoothing factor", factor)?;
+        self.config.rtt_smoothing_factor = factor;
+        Ok(())
+    }
+
+    /// Changes the smoothing factor of the packet loss, the factor must be between 0.0 and 1.0.
+    pub fn set_packet_loss_smoothing_factor(&mut self, factor: f32) -> Result<(), ConfigError> {
+        validate_smoothing_factor("packet loss smoothing factor", factor)?;
+        self.config.packet_loss_smoothing_factor = factor;
+        Ok(())
+    }
+
+    /// Returns the RTT measured by the last pong received, only available
+    /// when the ping interval is set in the connection config.
+    pub fn last_ping_rtt(&self) -> Option<Duration> {
+        self.last_ping_rtt
+    }
+
+    /// Returns the smoothed RTT variation (jitter) in milliseconds.
+    pub fn rtt_variance(&self) -> f32 {
+        self.rtt_variance
+    }
+
+    /// Returns the retransmission timeout calculated from the RTT and its variance,
+    /// returns zero while no RTT was measured.
+    pub fn retransmission_timeout(&self) -> Duration {
+        let timeout = self.rtt + 4. * self.rtt_variance;
+        Duration::from_micros((timeout * 1000.) as u64)
+    }
+
     pub fn packet_loss(&self) -> f32 {
         self.packet_loss
     }
 
+    /// Returns the network conditions detected by the congestion control,
+    /// always good when the congestion control is disabled.
+    pub fn congestion_state(&self) -> CongestionState {
+        self.congestion_state
+    }
+
+    /// Returns the number of sent packets that were not acked yet and are not considered lost,
+    /// sent less than 1.5x the RTT ago. Heartbeat packets are not considered.
+    pub fn packets_in_flight(&self) -> usize {
+        let mut packets_in_flight = 0;
+        for sent_packet in self.sent_buffer.entries() {
+            let millis_since_sent = (self.current_time - sent_packet.time).as_secs_f32() * 1000.;
+            let in_rtt_window = self.rtt < f32::EPSILON || millis_since_sent <= self.rtt * 1.5;
+            if !sent_packet.ack && in_rtt_window {
+                packets_in_flight += 1;
+            }
+        }
+
+        packets_in_flight
+    }
+
+    /// Returns the sequence that will be used by the next packet sent.
+    pub fn local_sequence(&self) -> u16 {
+        self.sequence
+    }
+
+    /// Returns the most recent received sequence and the bits acking the sequences before it,
+    /// as sent in the ack data of the next packet.
+    pub fn remote_ack_window(&self) -> (u16, u64) {
+        let ack_data = self.received_buffer.ack_data(self.config.ack_window_size);
+        (ack_data.ack, ack_data.ack_bits)
+    }
+
+    /// Returns the number of duplicated fragments received and discarded.
+    pub fn duplicate_fragments(&self) -> u64 {
+        self.duplicate_fragments
+    }
+
+    /// Returns the time left before a heartbeat is sent, if no other packet is sent in the meantime.
+    pub fn time_until_heartbeat(&self) -> Duration {
+        self.heartbeat_timer.remaining(self.current_time)
+    }
+
+    /// Returns the time since packets were last returned by [get_packets_to_send][Self::get_packets_to_send].
+    /// A value much higher than the heartbeat time means that packets are not being sent often enough.
+    pub fn time_since_last_send(&self) -> Duration {
+        self.current_time.saturating_sub(self.last_send_time)
+    }
+
+    /// Returns the statistics of the reassembly of fragmented packets.
+    pub fn reassembly_stats(&self) -> ReassemblyStats {
+        ReassemblyStats {
+            buffered: self.reassembly_buffer.entries().count(),
+            completed: self.reassembled_packets,
+            evicted: self.evicted_reassembly_packets,
+        }
+    }
+
+    /// Returns the lifetime totals of the packets sent, received and acked.
+    pub fn totals(&self) -> ConnectionTotals {
+        self.totals
+    }
+
+    /// Returns the number of packets received of each kind.
+    pub fn packet_type_stats(&self) -> PacketTypeStats {
+        self.packet_type_stats
+    }
+
+    /// Returns the statistics of the messages coalesced in the sent packets.
+    pub fn coalesce_stats(&self) -> CoalesceStats {
+        if self.coalesced_packets == 0 {
+            return CoalesceStats::default();
+        }
+
+        let packets = self.coalesced_packets as f32;
+        CoalesceStats {
+            packets: self.coalesced_packets,
+            messages_per_packet: self.coalesced_messages as f32 / packets,
+            fill_ratio: (self.coalesced_bytes as f32 / (packets * self.fragment_above() as f32)).min(1.0),
+        }
+    }
+
+    /// Returns the number of packets received again, duplicated or replayed, and discarded.
+    pub fn duplicate_packets(&self) -> u64 {
+        self.duplicate_packets
+    }
+
+    /// Returns the number of messages received for channels that were removed or never existed,
+    /// these messages are ignored.
+    pub fn unknown_channel_messages(&self) -> u64 {
+        self.unknown_channel_messages
+    }
+
+    /// Returns the number of received datagrams dropped because too many were waiting to be read.
+    pub fn dropped_datagrams(&self) -> u64 {
+        self.dropped_datagrams
+    }
+
+    /// Returns the bandwidth used by the channel, calculated from the packets in the sent and
+    /// received buffers. Only the serialized channel data is considered, not the packet header.
+    /// Returns None if the channel does not exist.
+    pub fn channel_bandwidth<I: Into<u8>>(&self, channel_id: I) -> Option<ChannelBandwidth> {
+        let channel_id = channel_id.into();
+        if !self.send_channels.contains_key(&channel_id) && !self.receive_channels.contains_key(&channel_id) {
+            return None;
+        }
+
+        // Both directions use the same window, all the packets still in the buffers
+        let sent_packets = self.sent_buffer.entries().map(|packet| (packet.time, &packet.channels_size[..]));
+        let sent_kbps = channel_kilobits_per_second(channel_id, sent_packets);
+
+        let received_packets = self
+            .received_buffer
+            .entries()
+            .map(|packet| (packet.time, &packet.channels_size[..]));
+        let received_kbps = channel_kilobits_per_second(channel_id, received_packets);
+
+        Some(ChannelBandwidth { sent_kbps, received_kbps })
+    }
+
     pub fn is_connected(&self) -> bool {
         matches!(self.state, ConnectionState::Connected)
     }
@@ -144,297 +885,2612 @@ impl RemoteConnection {
             return;
         }
 
-        self.state = ConnectionState::Disconnected {
-            reason: DisconnectionReason::DisconnectedByClient,
-        };
+        self.set_disconnected(DisconnectionReason::DisconnectedByClient);
     }
 
-    pub fn can_send_message<I: Into<u8>>(&self, channel_id: I) -> bool {
-        let channel = self.send_channels.get(&channel_id.into()).expect("invalid channel id");
-        channel.can_send_message()
-    }
+    /// Disconnects the connection and returns the packets that should be sent to the remote
+    /// connection, so it can be notified of the disconnection and its reason.
+    /// The disconnect packet is repeated a few times to survive packet loss.
+    pub fn disconnect_with_reason(&mut self, reason: DisconnectionReason) -> Result<Vec<Payload>, RechannelError> {
+        if let Some(reason) = self.disconnected() {
+            return Err(RechannelError::ClientDisconnected(reason));
+        }
 
-    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
-        let channel = self.send_channels.get_mut(&channel_id.into()).expect("invalid channel id");
-        channel.send_message(message.into(), self.current_time);
+        self.set_disconnected(reason);
+
+        let packet = Packet::Disconnect { reason };
+        let packet = self.serializer.serialize(&packet)?;
+
+        Ok(vec![packet; NUM_DISCONNECT_PACKETS_TO_SEND])
     }
 
-    pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Payload> {
-        let channel = self.receive_channels.get_mut(&channel_id.into()).expect("invalid channel id");
-        channel.receive_message()
+    /// Returns the ids of the channels that can send messages, sorted.
+    pub fn send_channels_id(&self) -> Vec<u8> {
+        let mut channels_id: Vec<u8> = self.send_channels.keys().copied().collect();
+        channels_id.sort_unstable();
+        channels_id
     }
 
-    pub fn advance_time(&mut self, duration: Duration) {
-        self.current_time += duration;
+    /// Returns the ids of the channels that can receive messages, sorted.
+    pub fn receive_channels_id(&self) -> Vec<u8> {
+        let mut channels_id: Vec<u8> = self.receive_channels.keys().copied().collect();
+        channels_id.sort_unstable();
+        channels_id
     }
 
-    pub fn update(&mut self) -> Result<(), RechannelError> {
-        if let Some(reason) = self.disconnected() {
-            return Err(RechannelError::ClientDisconnected(reason));
+    /// Removes the send and receive channels with the given id, returns false if there was no channel.
+    /// Messages already in flight for the removed channel are ignored when received.
+    pub fn remove_channel<I: Into<u8>>(&mut self, channel_id: I) -> bool {
+        let channel_id = channel_id.into();
+        let removed_send = self.send_channels.remove(&channel_id).is_some();
+        let removed_receive = self.receive_channels.remove(&channel_id).is_some();
+        if removed_receive {
+            self.removed_channels.insert(channel_id);
         }
 
-        for (&channel_id, send_channel) in self.send_channels.iter() {
-            if let Some(error) = send_channel.error() {
-                let reason = DisconnectionReason::SendChannelError { channel_id, error };
-                self.state = ConnectionState::Disconnected { reason };
-                return Err(RechannelError::ClientDisconnected(reason));
-            }
-        }
+        removed_send || removed_receive
+    }
 
-        for (&channel_id, receive_channel) in self.receive_channels.iter() {
-            if let Some(error) = receive_channel.error() {
-                let reason = DisconnectionReason::ReceiveChannelError { channel_id, error };
-                self.state = ConnectionState::Disconnected { reason };
-                return Err(RechannelError::ClientDisconnected(reason));
+    /// Resets the connection to its initial state, so it can be reused for a new connection
+    /// with a fresh remote connection. The packet buffers are cleared without being reallocated,
+    /// and the channels are recreated from the configuration, except the removed ones.
+    pub fn reset(&mut self) {
+        self.state = ConnectionState::Connected;
+        self.sequence = self.config.initial_sequence;
+        self.reassembly_buffer.reset();
+        self.sent_buffer.reset();
+        self.received_buffer.reset();
+        self.heartbeat_timer.reset(self.current_time);
+        self.last_send_time = self.current_time;
+        self.ping_timer.reset(self.current_time);
+        self.pong_to_send = None;
+        self.ping_sent_time = None;
+        self.last_ping_rtt = None;
+        self.mtu_probe = MtuProbe::new(&self.config);
+        self.mtu_probe_ack_to_send = None;
+        self.discovered_mtu = None;
+        self.rtt = 0.0;
+        self.rtt_variance = 0.0;
+        self.packet_loss = 0.0;
+        self.acks.clear();
+        self.nacks.clear();
+        self.pending_acks = 0;
+        self.duplicate_fragments = 0;
+        self.duplicate_packets = 0;
+        self.unknown_channel_messages = 0;
+        self.dropped_datagrams = 0;
+        self.reassembled_packets = 0;
+        self.evicted_reassembly_packets = 0;
+        self.coalesced_packets = 0;
+        self.coalesced_messages = 0;
+        self.coalesced_bytes = 0;
+        self.totals = ConnectionTotals::default();
+        self.packet_type_stats = PacketTypeStats::default();
+        self.datagrams_to_send.clear();
+        self.received_datagrams.clear();
+        self.delivered_messages.clear();
+        self.expired_messages.clear();
+        self.send_budget = self.config.max_packet_size as f32;
+        self.congestion_state = CongestionState::Good;
+        self.congestion_recovery_timer.reset(self.current_time);
+
+        for channel_config in self.config.send_channels_config.iter() {
+            let channel_id = channel_config.channel_id();
+            if self.send_channels.contains_key(&channel_id) {
+                let (send_channel, _) = channel_config.new_channels();
+                self.send_channels.insert(channel_id, send_channel);
             }
         }
 
-        for ack in self.acks.drain(..) {
-            for channel in self.send_channels.values_mut() {
-                channel.process_ack(ack);
+        for channel_config in self.config.receive_channels_config.iter() {
+            let channel_id = channel_config.channel_id();
+            if self.receive_channels.contains_key(&channel_id) {
+                let (_, receive_channel) = channel_config.new_channels();
+                self.receive_channels.insert(channel_id, receive_channel);
             }
         }
+    }
 
-        self.update_packet_loss();
+    /// Returns the progress of the message being sent in a chunk channel.
+    /// Returns None if no message is being sent or the channel is not a chunk channel.
+    pub fn chunk_progress<I: Into<u8>>(&self, channel_id: I) -> Option<ChunkProgress> {
+        let channel = self.send_channels.get(&channel_id.into())?;
+        channel.chunk_progress()
+    }
 
-        Ok(())
+    pub fn can_send_message<I: Into<u8>>(&self, channel_id: I) -> bool {
+        let channel = self.send_channels.get(&channel_id.into()).expect("invalid channel id");
+        channel.can_send_message()
     }
 
-    pub fn process_packet(&mut self, packet: &[u8]) -> Result<(), RechannelError> {
-        if let Some(reason) = self.disconnected() {
-            return Err(RechannelError::ClientDisconnected(reason));
-        }
+    /// Returns true if there are messages or datagrams ready to be sent in the next packets,
+    /// without generating the packets. Reliable messages waiting for an ack are only
+    /// counted when they are due to be resent.
+    pub fn has_data_to_send(&self) -> bool {
+        !self.datagrams_to_send.is_empty()
+            || self
+                .send_channels
+                .values()
+                .any(|channel| channel.has_messages_ready(self.current_time))
+    }
 
-        let packet: Packet = bincode::options().deserialize(packet)?;
+    /// Returns the number of messages in the channel waiting to be sent,
+    /// or waiting to be acked for reliable channels.
+    pub fn pending_messages<I: Into<u8>>(&self, channel_id: I) -> usize {
+        let channel = self.send_channels.get(&channel_id.into()).expect("invalid channel id");
+        channel.pending_messages()
+    }
 
-        let channels_packet_data = match packet {
-            Packet::Normal {
-                sequence,
+    /// Returns the number of bytes of the messages in the channel waiting to be sent,
+    /// or waiting to be acked for reliable channels.
+    pub fn pending_bytes<I: Into<u8>>(&self, channel_id: I) -> usize {
+        let channel = self.send_channels.get(&channel_id.into()).expect("invalid channel id");
+        channel.pending_bytes()
+    }
+
+    /// Send a message over a channel, logs an error if the channel does not exist.
+    /// A message above the channel max message size errors the channel, disconnecting the connection,
+    /// use [try_send_message][Self::try_send_message] to handle it instead.
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        if self.disconnected().is_none() {
+            if let Some(channel) = self.send_channels.get_mut(&channel_id) {
+                if message.len() as u64 > channel.max_message_size() {
+                    channel.send_message(message, self.current_time);
+                    return;
+                }
+            }
+        }
+
+        if let Err(e) = self.try_send_message(channel_id, message) {
+            log::error!("Failed to send message: {}", e);
+        }
+    }
+
+    /// Send the same message over multiple channels, logs an error for each channel that fails.
+    /// The message buffer is shared between the channels instead of being copied.
+    pub fn send_message_multi<B: Into<Bytes>>(&mut self, channel_ids: &[u8], message: B) {
+        let message = message.into();
+        for &channel_id in channel_ids {
+            self.send_message(channel_id, message.clone());
+        }
+    }
+
+    /// Send a message over a channel, returns an error if the connection is disconnected,
+    /// the channel does not exist or if the message is above the channel max message size.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), RechannelError> {
+        self.try_send_message_with_priority(channel_id, message, 0)
+    }
+
+    /// Send a message over a channel with a priority, when not all messages fit in a packet
+    /// the ones with higher priority are sent first. Only reliable channels use the priority,
+    /// lower priority messages are still sent in the next packets.
+    pub fn try_send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        channel_id: I,
+        message: B,
+        priority: u8,
+    ) -> Result<(), RechannelError> {
+        if let Some(reason) = self.disconnected() {
+            return Err(RechannelError::ClientDisconnected(reason));
+        }
+
+        let channel_id = channel_id.into();
+        let channel = self
+            .send_channels
+            .get_mut(&channel_id)
+            .ok_or(RechannelError::InvalidChannelId(channel_id))?;
+
+        let message = message.into();
+        let max = channel.max_message_size();
+        if message.len() as u64 > max {
+            return Err(RechannelError::MessageTooLarge {
+                channel_id,
+                size: message.len(),
+                max,
+            });
+        }
+
+        if message.is_empty() && !channel.accepts_empty_messages() {
+            return Err(RechannelError::EmptyMessage { channel_id });
+        }
+
+        if let Some(max_queued_bytes) = channel.max_queued_bytes() {
+            if channel.pending_bytes() + message.len() > max_queued_bytes {
+                return Err(RechannelError::ChannelFull { channel_id });
+            }
+        }
+
+        channel.send_message_with_priority(message, priority, self.current_time);
+
+        Ok(())
+    }
+
+    /// Returns an iterator over the messages received in all channels, with their channel id.
+    /// The channels are drained in the order of their ids, messages not iterated stay in the channels.
+    pub fn drain_received(&mut self) -> impl Iterator<Item = (u8, Payload)> + '_ {
+        let mut channels: Vec<_> = self.receive_channels.iter_mut().collect();
+        channels.sort_by_key(|(&channel_id, _)| channel_id);
+
+        channels.into_iter().flat_map(|(&channel_id, channel)| {
+            std::iter::from_fn(move || channel.receive_message()).map(move |message| (channel_id, message))
+        })
+    }
+
+    /// Send a message over a reliable channel and returns its id, the id is reported
+    /// by [delivered_messages][Self::delivered_messages] once the message is acked.
+    pub fn try_send_message_with_id<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        channel_id: I,
+        message: B,
+    ) -> Result<MessageId, RechannelError> {
+        let channel_id = channel_id.into();
+        let channel = self
+            .send_channels
+            .get(&channel_id)
+            .ok_or(RechannelError::InvalidChannelId(channel_id))?;
+        let id = channel.next_message_id().ok_or(RechannelError::DeliveryNotTracked(channel_id))?;
+
+        self.try_send_message(channel_id, message)?;
+        Ok(MessageId { channel_id, id })
+    }
+
+    /// Returns the reliable messages acked in the last update,
+    /// their ids are returned by [try_send_message_with_id][Self::try_send_message_with_id].
+    pub fn delivered_messages(&self) -> &[MessageId] {
+        &self.delivered_messages
+    }
+
+    /// Returns the reliable messages that expired in the last update without being acked,
+    /// only channels with a message TTL expire their messages.
+    pub fn expired_messages(&self) -> &[MessageId] {
+        &self.expired_messages
+    }
+
+    /// Returns the next message received in the channel, or None if there is none
+    /// or the channel does not exist.
+    pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Payload> {
+        let channel_id = channel_id.into();
+        match self.receive_channels.get_mut(&channel_id) {
+            Some(channel) => channel.receive_message(),
+            None => {
+                log::debug!("Tried to receive message from invalid channel {}", channel_id);
+                None
+            }
+        }
+    }
+
+    /// Send an unreliable datagram outside the channels, it is sent in its own packet
+    /// in the next [get_packets_to_send][Self::get_packets_to_send] call.
+    /// Datagrams are not fragmented, so their packet, header included, cannot be above the fragmentation threshold.
+    pub fn send_unreliable<B: Into<Bytes>>(&mut self, payload: B) -> Result<(), RechannelError> {
+        if let Some(reason) = self.disconnected() {
+            return Err(RechannelError::ClientDisconnected(reason));
+        }
+
+        let payload = payload.into();
+        let max = self.config.fragment_config.fragment_above;
+        let size = self.serializer.serialized_size(&Packet::Datagram { payload: payload.clone() })?;
+        if size > max {
+            return Err(RechannelError::DatagramTooLarge { size: size as usize, max });
+        }
+
+        self.datagrams_to_send.push(payload);
+        Ok(())
+    }
+
+    /// Receive an unreliable datagram sent outside the channels.
+    pub fn receive_unreliable(&mut self) -> Option<Payload> {
+        self.received_datagrams.pop_front()
+    }
+
+    /// Pauses the connection, the time advanced while paused is ignored so no heartbeats,
+    /// resends or timeouts happen until the connection is resumed.
+    pub fn pause(&mut self) {
+        self.heartbeat_timer.pause(self.current_time);
+    }
+
+    /// Resumes the connection from where it was paused.
+    pub fn resume(&mut self) {
+        self.heartbeat_timer.resume(self.current_time);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.heartbeat_timer.is_paused()
+    }
+
+    pub fn advance_time(&mut self, duration: Duration) {
+        if self.is_paused() {
+            return;
+        }
+
+        self.current_time += duration;
+        if let Some(send_bandwidth_kbps) = self.config.send_bandwidth_kbps(self.congestion_state) {
+            let bytes = send_bandwidth_kbps * 1000. / 8. * duration.as_secs_f32();
+            self.send_budget = (self.send_budget + bytes).min(self.config.max_packet_size as f32);
+        }
+    }
+
+    pub fn update(&mut self) -> Result<(), RechannelError> {
+        if let Some(reason) = self.disconnected() {
+            return Err(RechannelError::ClientDisconnected(reason));
+        }
+
+        for (&channel_id, send_channel) in self.send_channels.iter() {
+            if let Some(error) = send_channel.error() {
+                let reason = DisconnectionReason::SendChannelError { channel_id, error };
+                self.set_disconnected(reason);
+                return Err(RechannelError::ClientDisconnected(reason));
+            }
+        }
+
+        for (&channel_id, receive_channel) in self.receive_channels.iter() {
+            if let Some(error) = receive_channel.error() {
+                let reason = DisconnectionReason::ReceiveChannelError { channel_id, error };
+                self.set_disconnected(reason);
+                return Err(RechannelError::ClientDisconnected(reason));
+            }
+        }
+
+        for (ack, channel_id) in self.acks.drain(..) {
+            if let Some(channel) = self.send_channels.get_mut(&channel_id) {
+                channel.process_ack(ack);
+            }
+        }
+
+        for (nack, channel_id) in self.nacks.drain(..) {
+            if let Some(channel) = self.send_channels.get_mut(&channel_id) {
+                channel.process_nack(nack);
+            }
+        }
+
+        self.delivered_messages.clear();
+        for (&channel_id, channel) in self.send_channels.iter_mut() {
+            for id in channel.take_delivered_messages() {
+                self.delivered_messages.push(MessageId { channel_id, id });
+            }
+        }
+
+        self.expired_messages.clear();
+        for (&channel_id, channel) in self.send_channels.iter_mut() {
+            for id in channel.take_expired_messages() {
+                self.expired_messages.push(MessageId { channel_id, id });
+            }
+        }
+
+        if self.rtt > f32::EPSILON {
+            let retransmission_timeout = self.retransmission_timeout();
+            for channel in self.send_channels.values_mut() {
+                channel.set_retransmission_timeout(retransmission_timeout);
+            }
+        }
+
+        self.update_packet_loss();
+        self.update_congestion_state();
+
+        Ok(())
+    }
+
+    pub fn process_packet(&mut self, packet: &[u8]) -> Result<ProcessSummary, RechannelError> {
+        let mut summary = ProcessSummary {
+            bytes: packet.len(),
+            ..Default::default()
+        };
+
+        let channels_packet_data = match self.receive_packet(packet, false, &mut summary)? {
+            Some(ReceivedPayload::Channels(channels_packet_data)) => channels_packet_data,
+            _ => return Ok(summary),
+        };
+
+        for channel_packet_data in channels_packet_data.into_iter() {
+            let receive_channel = match self.receive_channels.get_mut(&channel_packet_data.channel_id) {
+                Some(c) => c,
+                None => {
+                    if self.removed_channels.contains(&channel_packet_data.channel_id) {
+                        log::trace!("Ignored messages for removed channel {}", channel_packet_data.channel_id);
+                    } else {
+                        log::debug!("Ignored messages for unknown channel {}", channel_packet_data.channel_id);
+                    }
+                    self.unknown_channel_messages += channel_packet_data.messages.len() as u64;
+                    continue;
+                }
+            };
+
+            summary
+                .messages
+                .push((channel_packet_data.channel_id, channel_packet_data.messages.len()));
+            receive_channel.process_messages(channel_packet_data.messages);
+        }
+
+        Ok(summary)
+    }
+
+    /// Processes the packet like [RemoteConnection::process_packet], but instead of delivering
+    /// its messages to the channels, returns the reassembled application payload: the serialized
+    /// channels data of the packet. Acks, duplicates and fragments are still handled.
+    /// Returns None when the packet has no application payload or is an incomplete fragment.
+    pub fn process_packet_raw(&mut self, packet: &[u8]) -> Result<Option<Box<[u8]>>, RechannelError> {
+        let mut summary = ProcessSummary::default();
+        match self.receive_packet(packet, true, &mut summary)? {
+            Some(ReceivedPayload::Raw(payload)) => Ok(Some(payload.into_boxed_slice())),
+            _ => Ok(None),
+        }
+    }
+
+    fn receive_packet(
+        &mut self,
+        packet: &[u8],
+        raw: bool,
+        summary: &mut ProcessSummary,
+    ) -> Result<Option<ReceivedPayload>, RechannelError> {
+        if let Some(reason) = self.disconnected() {
+            return Err(RechannelError::ClientDisconnected(reason));
+        }
+
+        self.totals.packets_received += 1;
+        self.totals.bytes_received += packet.len() as u64;
+
+        // The payload of normal packets is returned as it was received, only the header is deserialized.
+        // Other packets, or serializers that can't deserialize only the header, continue below.
+        if raw {
+            if let Ok(Some((PacketHeader::Normal { sequence, ack_data }, header_size))) = self.serializer.deserialize_prefix(packet) {
+                self.packet_type_stats.normal += 1;
+                if self.discard_received_packet(sequence) {
+                    return Ok(None);
+                }
+
+                // Raw payloads are not deserialized, so their size is not split by channel
+                self.received_buffer
+                    .insert(sequence, ReceivedPacket::new(self.current_time, vec![]));
+                self.pending_acks += 1;
+                summary.acks = self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+                return Ok(Some(ReceivedPayload::Raw(packet[header_size..].to_vec())));
+            }
+        }
+
+        let packet: Packet = self.serializer.deserialize(packet)?;
+        let stats = &mut self.packet_type_stats;
+        match packet {
+            Packet::Normal { .. } => stats.normal += 1,
+            Packet::Fragment { .. } => stats.fragment += 1,
+            Packet::Heartbeat { .. } => stats.heartbeat += 1,
+            Packet::Disconnect { .. } => stats.disconnect += 1,
+            Packet::Datagram { .. } => stats.datagram += 1,
+            Packet::Ping { .. } | Packet::Pong { .. } => stats.ping += 1,
+            Packet::MtuProbe { .. } | Packet::MtuProbeAck { .. } => stats.mtu_probe += 1,
+        }
+
+        let payload = match packet {
+            Packet::Normal {
+                sequence,
                 ack_data,
                 channels_packet_data,
             } => {
-                self.received_buffer.insert(sequence, ());
-                self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
-                channels_packet_data
+                if self.discard_received_packet(sequence) {
+                    return Ok(None);
+                }
+
+                let received_packet = ReceivedPacket::new(self.current_time, channels_size(&channels_packet_data, &self.serializer)?);
+                self.received_buffer.insert(sequence, received_packet);
+                self.pending_acks += 1;
+                summary.acks = self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+                match raw {
+                    true => ReceivedPayload::Raw(self.serializer.serialize(&channels_packet_data)?),
+                    false => ReceivedPayload::Channels(channels_packet_data),
+                }
+            }
+            Packet::Fragment {
+                sequence,
+                ack_data,
+                fragment_data,
+            } => {
+                // The reassembled packets are removed from the reassembly buffer,
+                // so their replayed fragments would be reassembled and processed again
+                if self.received_buffer.exists(sequence) || self.received_buffer.is_stale(sequence) {
+                    let fragment_id = fragment_data.fragment_id;
+                    self.observer
+                        .on_packet_dropped(DroppedPacket::DuplicatedFragment { sequence, fragment_id });
+                    self.duplicate_fragments += 1;
+                    return Ok(None);
+                }
+
+                summary.acks = self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+
+                // The first fragment of a packet can evict older incomplete packets from the buffer
+                let buffered = match self.reassembly_buffer.exists(sequence) {
+                    true => None,
+                    false => Some(self.reassembly_buffer.entries().count()),
+                };
+
+                let max_packet_size = self.config.max_packet_size;
+                let fragment_config = &self.config.fragment_config;
+                let payload = match raw {
+                    true => self
+                        .reassembly_buffer
+                        .handle_fragment_raw(sequence, fragment_data, max_packet_size, fragment_config)
+                        .map(|payload| payload.map(ReceivedPayload::Raw)),
+                    false => self
+                        .reassembly_buffer
+                        .handle_fragment(sequence, fragment_data, max_packet_size, fragment_config, &self.serializer)
+                        .map(|payload| payload.map(ReceivedPayload::Channels)),
+                };
+
+                if let Some(buffered) = buffered {
+                    let inserted = usize::from(self.reassembly_buffer.exists(sequence));
+                    let evicted = (buffered + inserted).saturating_sub(self.reassembly_buffer.entries().count());
+                    if evicted > 0 {
+                        self.observer.on_packet_dropped(DroppedPacket::EvictedFragments { count: evicted });
+                        self.evicted_reassembly_packets += evicted as u64;
+                    }
+                }
+                match payload {
+                    Err(FragmentError::AlreadyProcessed { sequence, id }) => {
+                        // Duplicated fragments are expected when the network duplicates packets
+                        self.observer
+                            .on_packet_dropped(DroppedPacket::DuplicatedFragment { sequence, fragment_id: id });
+                        self.duplicate_fragments += 1;
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(e.into()),
+                    Ok(None) => return Ok(None),
+                    Ok(Some(payload)) => {
+                        self.reassembled_packets += 1;
+                        // Raw payloads are not deserialized, so their size is not split by channel
+                        let channels_size = match payload {
+                            ReceivedPayload::Channels(ref channels_packet_data) => channels_size(channels_packet_data, &self.serializer)?,
+                            ReceivedPayload::Raw(_) => vec![],
+                        };
+
+                        // Only consider the packet received when the fragment is completed
+                        let received_packet = ReceivedPacket::new(self.current_time, channels_size);
+                        self.received_buffer.insert(sequence, received_packet);
+                        self.pending_acks += 1;
+                        payload
+                    }
+                }
+            }
+            Packet::Heartbeat { ack_data } => {
+                summary.heartbeat = true;
+                summary.acks = self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+                return Ok(None);
+            }
+            Packet::Disconnect { reason } => {
+                self.set_disconnected(reason);
+                return Ok(None);
+            }
+            Packet::Datagram { payload } => {
+                if self.received_datagrams.len() >= MAX_RECEIVED_DATAGRAMS {
+                    self.received_datagrams.pop_front();
+                    self.dropped_datagrams += 1;
+                }
+                self.received_datagrams.push_back(payload.into());
+                return Ok(None);
+            }
+            Packet::Ping { time } => {
+                self.pong_to_send = Some(time);
+                return Ok(None);
+            }
+            Packet::Pong { time } => {
+                if self.ping_sent_time == Some(time) {
+                    self.ping_sent_time = None;
+                    let rtt = self.current_time.saturating_sub(Duration::from_micros(time));
+                    self.last_ping_rtt = Some(rtt);
+                    self.update_rtt(rtt.as_secs_f32() * 1000.);
+                }
+                return Ok(None);
+            }
+            Packet::MtuProbe { size, .. } => {
+                self.mtu_probe_ack_to_send = Some(size);
+                return Ok(None);
+            }
+            Packet::MtuProbeAck { size } => {
+                if let Some(mtu_probe) = &mut self.mtu_probe {
+                    if matches!(mtu_probe.in_flight, Some((probe_size, _)) if probe_size == size) {
+                        mtu_probe.low = size;
+                        mtu_probe.in_flight = None;
+                    }
+                }
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(payload))
+    }
+
+    /// Advances the MTU binary search, returns the size of the next probe to send.
+    fn mtu_probe_to_send(&mut self) -> Option<u64> {
+        let mtu_config = self.config.mtu_discovery.as_ref()?;
+        let mtu_probe = self.mtu_probe.as_mut()?;
+        if let Some((size, sent_time)) = mtu_probe.in_flight {
+            if self.current_time.saturating_sub(sent_time) < mtu_config.probe_timeout {
+                return None;
+            }
+            mtu_probe.high = size;
+            mtu_probe.in_flight = None;
+        }
+
+        if mtu_probe.high.saturating_sub(mtu_probe.low) <= mtu_config.precision {
+            log::debug!("Discovered path MTU of {} bytes", mtu_probe.low);
+            self.discovered_mtu = Some(mtu_probe.low);
+            self.mtu_probe = None;
+            return None;
+        }
+
+        let size = (mtu_probe.low + mtu_probe.high) / 2;
+        mtu_probe.in_flight = Some((size, self.current_time));
+        Some(size)
+    }
+
+    /// Returns the packets that should be sent to the remote connection.
+    /// All pending messages from every channel are coalesced into a single packet, limited by
+    /// `max_packet_size`. When this packet is bigger than the `fragment_above` from the
+    /// fragment config, it's split into fragments, so `fragment_above` should be kept under the path MTU,
+    /// or lowered with the MTU discovery.
+    pub fn get_packets_to_send(&mut self) -> Result<Vec<Payload>, RechannelError> {
+        let mut packets = vec![];
+        self.for_each_packet_to_send(|packet| packets.push(packet.to_vec()))?;
+
+        Ok(packets)
+    }
+
+    /// Same as [get_packets_to_send][Self::get_packets_to_send], but the packets are serialized
+    /// into a buffer reused between calls and passed to `send`, instead of being returned.
+    /// The packet is only valid during the call, so `send` should write it to the socket.
+    ///
+    /// A heartbeat is sent without allocations, where [get_packets_to_send][Self::get_packets_to_send]
+    /// allocates the returned vector and the packet. Packets with messages still allocate the
+    /// messages taken from the channels, and fragmented packets allocate each fragment.
+    pub fn for_each_packet_to_send<F: FnMut(&[u8])>(&mut self, mut send: F) -> Result<(), RechannelError> {
+        if let Some(reason) = self.disconnected() {
+            return Err(RechannelError::ClientDisconnected(reason));
+        }
+
+        let mut sent_packets = 0;
+        let mut sent_bytes = 0;
+        let mut send = |packet: &[u8]| {
+            sent_packets += 1;
+            sent_bytes += packet.len();
+            send(packet);
+        };
+
+        for payload in self.datagrams_to_send.drain(..) {
+            let packet = Packet::Datagram { payload };
+            send(serialize_packet(&self.serializer, &mut self.send_buffer, &packet)?);
+        }
+
+        if let Some(time) = self.pong_to_send.take() {
+            send(serialize_packet(&self.serializer, &mut self.send_buffer, &Packet::Pong { time })?);
+        }
+
+        if self.config.ping_interval.is_some() && self.ping_timer.is_finished(self.current_time) {
+            self.ping_timer.reset(self.current_time);
+            let time = self.current_time.as_micros() as u64;
+            self.ping_sent_time = Some(time);
+            send(serialize_packet(&self.serializer, &mut self.send_buffer, &Packet::Ping { time })?);
+        }
+
+        if let Some(size) = self.mtu_probe_ack_to_send.take() {
+            send(serialize_packet(
+                &self.serializer,
+                &mut self.send_buffer,
+                &Packet::MtuProbeAck { size },
+            )?);
+        }
+
+        if let Some(size) = self.mtu_probe_to_send() {
+            let padding = vec![0; size.saturating_sub(MTU_PROBE_HEADER_SIZE) as usize];
+            send(serialize_packet(
+                &self.serializer,
+                &mut self.send_buffer,
+                &Packet::MtuProbe { size, padding },
+            )?);
+        }
+
+        let fragment_above = self.fragment_above();
+        let mut available_bytes = self.config.max_packet_size - PACKET_HEADER_SIZE;
+        if self.config.send_bandwidth_kbps(self.congestion_state).is_some() {
+            available_bytes = available_bytes.min((self.send_budget as u64).saturating_sub(PACKET_HEADER_SIZE));
+        }
+        let mut sent_channels_data = false;
+        loop {
+            let sequence = self.sequence;
+            let mut packet_bytes = available_bytes;
+            if let Some(coalesce_mtu) = self.config.coalesce_mtu {
+                packet_bytes = packet_bytes
+                    .min(coalesce_mtu.saturating_sub(PACKET_HEADER_SIZE))
+                    .min(fragment_above);
+            }
+            let mut last_packet = self.config.coalesce_mtu.is_none();
+            let (mut channels_packet_data, mut channels_size) = self.collect_channels_data(packet_bytes, sequence)?;
+            if channels_packet_data.is_empty() && packet_bytes < available_bytes {
+                // Messages above the coalesce MTU are sent alone in the last packet
+                (channels_packet_data, channels_size) = self.collect_channels_data(available_bytes, sequence)?;
+                last_packet = true;
+            }
+            if channels_packet_data.is_empty() {
+                break;
+            }
+
+            self.sequence = self.sequence.wrapping_add(1);
+            let packet_size = self.serializer.serialized_size(&channels_packet_data)?;
+            let ack_data = self.packet_ack_data();
+            self.coalesced_packets += 1;
+            self.coalesced_messages += channels_packet_data.iter().map(|data| data.messages.len() as u64).sum::<u64>();
+            self.coalesced_bytes += packet_size;
+
+            let sent_packet = SentPacket::new(self.current_time, channels_size);
+            self.sent_buffer.insert(sequence, sent_packet);
+
+            if packet_size > fragment_above {
+                let fragments = build_fragments(
+                    &channels_packet_data,
+                    sequence,
+                    ack_data,
+                    &self.config.fragment_config,
+                    &self.serializer,
+                )?;
+                for fragment in fragments.iter() {
+                    send(fragment);
+                }
+            } else {
+                let packet = PacketRef::Normal {
+                    sequence,
+                    ack_data,
+                    channels_packet_data: &channels_packet_data,
+                };
+                send(serialize_packet(&self.serializer, &mut self.send_buffer, &packet)?);
+            }
+
+            sent_channels_data = true;
+            available_bytes = available_bytes.saturating_sub(packet_size + PACKET_HEADER_SIZE);
+            if last_packet {
+                break;
+            }
+        }
+
+        if sent_channels_data {
+            self.heartbeat_timer.reset(self.current_time);
+            self.pending_acks = 0;
+        } else if self.heartbeat_timer.is_finished(self.current_time) || self.should_flush_acks() {
+            let ack_data = self.packet_ack_data();
+            let packet = Packet::Heartbeat { ack_data };
+            send(serialize_packet(&self.serializer, &mut self.send_buffer, &packet)?);
+
+            self.heartbeat_timer.reset(self.current_time);
+            self.pending_acks = 0;
+        }
+
+        self.totals.packets_sent += sent_packets;
+        self.totals.bytes_sent += sent_bytes as u64;
+        if sent_packets > 0 {
+            // Heartbeats are sent after the heartbeat time, a much longer gap means that
+            // the packets were not generated often enough, and the remote may time out.
+            let time_since_last_send = self.time_since_last_send();
+            if time_since_last_send > self.config.heartbeat_time * 2 {
+                log::warn!(
+                    "No packets were sent for {:?}, above the heartbeat time of {:?}",
+                    time_since_last_send,
+                    self.config.heartbeat_time
+                );
+            }
+            self.last_send_time = self.current_time;
+        }
+
+        if self.config.send_bandwidth_kbps(self.congestion_state).is_some() {
+            self.send_budget = (self.send_budget - sent_bytes as f32).max(0.);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the packets needed to send all the queued data, instead of a single packet like
+    /// [RemoteConnection::get_packets_to_send]. Packets are generated until the channels have
+    /// nothing else to send or the bandwidth limit is reached.
+    pub fn flush_packets(&mut self) -> Result<Vec<Payload>, RechannelError> {
+        let mut packets = vec![];
+        loop {
+            let sequence = self.sequence;
+            packets.extend(self.get_packets_to_send()?);
+            // The sequence only advances when a packet with channels data was generated
+            if self.sequence == sequence {
+                return Ok(packets);
+            }
+        }
+    }
+
+    fn update_rtt(&mut self, rtt: f32) {
+        // The first sample initializes the RTT, all the next ones are smoothed
+        if self.rtt < f32::EPSILON {
+            self.rtt = rtt;
+            self.rtt_variance = rtt / 2.;
+        } else {
+            // The variance is updated with the previous smoothed RTT, like in the TCP RTO calculation
+            let deviation = (rtt - self.rtt).abs();
+            self.rtt_variance += (deviation - self.rtt_variance) * self.config.rtt_smoothing_factor;
+            self.rtt += (rtt - self.rtt) * self.config.rtt_smoothing_factor;
+        }
+    }
+
+    fn should_flush_acks(&self) -> bool {
+        match self.config.max_pending_acks {
+            Some(max_pending_acks) => self.pending_acks >= max_pending_acks,
+            None => false,
+        }
+    }
+
+    // Messages from every channel that fit in the available bytes, with the size of each channel data
+    fn collect_channels_data(
+        &mut self,
+        mut available_bytes: u64,
+        sequence: u16,
+    ) -> Result<(Vec<ChannelPacketData>, ChannelsSize), SerializationError> {
+        let mut channels_packet_data = vec![];
+        let mut channels_size = vec![];
+        for send_channel in self.send_channels.values_mut() {
+            if let Some(channel_packet_data) = send_channel.get_messages_to_send(available_bytes, sequence, self.current_time) {
+                let channel_size = self.serializer.serialized_size(&channel_packet_data)?;
+                available_bytes -= channel_size;
+                channels_size.push((channel_packet_data.channel_id, channel_size));
+                channels_packet_data.push(channel_packet_data);
+            }
+        }
+
+        Ok((channels_packet_data, channels_size))
+    }
+
+    // Size above which the packets are fragmented, lowered to fit the discovered MTU
+    fn fragment_above(&self) -> u64 {
+        let fragment_above = self.config.fragment_config.fragment_above;
+        match self.discovered_mtu {
+            Some(mtu) => fragment_above.min(mtu.saturating_sub(PACKET_HEADER_SIZE)),
+            None => fragment_above,
+        }
+    }
+
+    // Discard the packet before acting on it, so its messages are not processed twice.
+    // Packets older than the received buffer can't be told apart from replayed ones.
+    fn discard_received_packet(&mut self, sequence: u16) -> bool {
+        if self.received_buffer.exists(sequence) || self.received_buffer.is_stale(sequence) {
+            self.observer.on_packet_dropped(DroppedPacket::Duplicated { sequence });
+            self.duplicate_packets += 1;
+            return true;
+        }
+
+        false
+    }
+
+    // Ack data sent in the packets
+    fn packet_ack_data(&self) -> AckData {
+        let ack_data = self.received_buffer.ack_data(self.config.ack_window_size);
+        match self.config.compact_acks {
+            true => ack_data.invert_ack_bits(self.config.ack_window_size),
+            false => ack_data,
+        }
+    }
+
+    /// Marks the sent packets as acked, returns the number of packets that were newly acked.
+    fn update_acket_packets(&mut self, ack: u16, mut ack_bits: u64) -> usize {
+        if self.config.compact_acks {
+            ack_bits = AckData { ack, ack_bits }.invert_ack_bits(self.config.ack_window_size).ack_bits;
+        }
+
+        // An ack newer than the last sent packet can only come from before the sequence wrapped,
+        // the packets in its window would be mistaken for the current ones with the same sequence.
+        let last_sent_sequence = self.sent_buffer.sequence().wrapping_sub(1);
+        if sequence_greater_than(ack, last_sent_sequence) {
+            log::debug!("Ignored stale ack {}, last sent packet has sequence {}", ack, last_sent_sequence);
+            return 0;
+        }
+
+        let received_bits = ack_bits;
+        let mut num_acks = 0;
+        for i in 0..64 {
+            if ack_bits & 1 != 0 {
+                let ack_sequence = ack.wrapping_sub(i);
+                if let Some(ref mut sent_packet) = self.sent_buffer.get_mut(ack_sequence) {
+                    if !sent_packet.ack {
+                        for &(channel_id, _) in sent_packet.channels_size.iter() {
+                            self.acks.push((ack_sequence, channel_id));
+                        }
+                        sent_packet.ack = true;
+                        num_acks += 1;
+                        self.totals.packets_acked += 1;
+
+                        let rtt = self.current_time - sent_packet.time;
+                        self.observer.on_ack(ack_sequence, rtt);
+                        self.update_rtt(rtt.as_secs_f32() * 1000.);
+                    }
+                }
+            }
+            ack_bits >>= 1;
+        }
+
+        if let Some(nack_threshold) = self.config.nack_threshold {
+            for i in nack_threshold as u16..self.config.ack_window_size as u16 {
+                if received_bits & (1 << i) != 0 {
+                    continue;
+                }
+
+                let nack_sequence = ack.wrapping_sub(i);
+                if let Some(sent_packet) = self.sent_buffer.get_mut(nack_sequence) {
+                    if !sent_packet.ack && !sent_packet.nack {
+                        for &(channel_id, _) in sent_packet.channels_size.iter() {
+                            self.nacks.push((nack_sequence, channel_id));
+                        }
+                        sent_packet.nack = true;
+                    }
+                }
+            }
+        }
+
+        num_acks
+    }
+
+    fn set_disconnected(&mut self, reason: DisconnectionReason) {
+        self.state = ConnectionState::Disconnected { reason };
+        self.observer.on_disconnected(reason);
+    }
+
+    fn update_congestion_state(&mut self) {
+        let congestion = match self.config.congestion_control.as_ref() {
+            Some(congestion) => congestion,
+            None => return,
+        };
+
+        let bad_conditions = self.packet_loss > congestion.max_packet_loss || self.rtt > congestion.max_rtt.as_secs_f32() * 1000.;
+        if bad_conditions {
+            if self.congestion_state == CongestionState::Good {
+                log::debug!("Congestion detected, packet loss {} and rtt {}ms", self.packet_loss, self.rtt);
+                self.congestion_state = CongestionState::Bad;
+            }
+            self.congestion_recovery_timer.reset(self.current_time);
+        } else if self.congestion_state == CongestionState::Bad && self.congestion_recovery_timer.is_finished(self.current_time) {
+            self.congestion_state = CongestionState::Good;
+        }
+    }
+
+    fn update_packet_loss(&mut self) {
+        // Without an RTT sample a lost packet cannot be told apart from one still in flight
+        if self.rtt < f32::EPSILON {
+            return;
+        }
+
+        let mut packets_dropped = 0;
+        let mut packets_sent = 0;
+        for sent_packet in self.sent_buffer.entries() {
+            packets_sent += 1;
+            let ms_since_sent = (self.current_time - sent_packet.time).as_secs_f32() * 1000.;
+            if !sent_packet.ack && ms_since_sent > self.rtt * 1.5 {
+                packets_dropped += 1;
+            }
+        }
+
+        if packets_sent == 0 {
+            return;
+        }
+
+        let packet_loss = packets_dropped as f32 / packets_sent as f32;
+        if self.packet_loss == 0.0 || self.packet_loss < f32::EPSILON {
+            self.packet_loss = packet_loss;
+        } else {
+            self.packet_loss += (packet_loss - self.packet_loss) * self.config.packet_loss_smoothing_factor;
+        }
+    }
+}
+
+fn validate_smoothing_factor(factor: &'static str, value: f32) -> Result<(), ConfigError> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(ConfigError::InvalidSmoothingFactor { factor });
+    }
+
+    Ok(())
+}
+
+fn channels_size<S: PacketSerializer>(
+    channels_packet_data: &[ChannelPacketData],
+    serializer: &S,
+) -> Result<Vec<(u8, u64)>, SerializationError> {
+    let mut channels_size = Vec::with_capacity(channels_packet_data.len());
+    for channel_packet_data in channels_packet_data.iter() {
+        let channel_size = serializer.serialized_size(channel_packet_data)?;
+        channels_size.push((channel_packet_data.channel_id, channel_size));
+    }
+
+    Ok(channels_size)
+}
+
+fn channel_kilobits_per_second<'a>(channel_id: u8, packets: impl Iterator<Item = (Duration, &'a [(u8, u64)])>) -> f32 {
+    let mut start = Duration::MAX;
+    let mut end = Duration::ZERO;
+    let mut bytes = 0;
+    for (time, channels_size) in packets {
+        for &(id, size) in channels_size.iter() {
+            if id != channel_id {
+                continue;
+            }
+            if time < start {
+                start = time;
+            }
+            if time > end {
+                end = time;
+            }
+
+            bytes += size;
+        }
+    }
+
+    if start >= end {
+        return 0.0;
+    }
+
+    let milli_seconds = (end - start).as_secs_f32() * 1000.0;
+    (bytes * 8) as f32 / milli_seconds
+}
+
+/// Serializes the packet into the buffer, replacing its content, and returns the serialized bytes.
+fn serialize_packet<'a, S: PacketSerializer, P: Serialize>(
+    serializer: &S,
+    buffer: &'a mut Vec<u8>,
+    packet: &P,
+) -> Result<&'a [u8], SerializationError> {
+    buffer.clear();
+    serializer.serialize_into(buffer, packet)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{channel::ReliableChannelConfig, error::ChannelError};
+
+    use super::*;
+
+    #[test]
+    fn round_time_trip() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        let message: Bytes = vec![1, 2, 3].into();
+        let mut ack_data = AckData { ack: 0, ack_bits: 1 };
+        for _ in 0..16 {
+            connection.send_message(1, message.clone());
+            assert!(!connection.get_packets_to_send().unwrap().is_empty());
+
+            connection.advance_time(Duration::from_millis(100));
+            connection.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+
+            ack_data.ack += 1;
+        }
+
+        assert_eq!(connection.rtt(), 100.);
+    }
+
+    #[test]
+    fn round_time_trip_smoothing() {
+        let config = ConnectionConfig {
+            rtt_smoothing_factor: 0.5,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+
+        let rtts = [100, 200, 200];
+        let expected_rtts = [100., 150., 175.];
+        for (ack, (rtt, expected_rtt)) in rtts.iter().zip(expected_rtts).enumerate() {
+            connection.send_message(1, vec![1, 2, 3]);
+            assert!(!connection.get_packets_to_send().unwrap().is_empty());
+
+            connection.advance_time(Duration::from_millis(*rtt));
+            connection.update_acket_packets(ack as u16, 1);
+            assert_eq!(connection.rtt(), expected_rtt);
+        }
+    }
+
+    #[test]
+    fn round_time_trip_steady_state() {
+        let config = ConnectionConfig {
+            rtt_smoothing_factor: 0.5,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+
+        // Samples close to the current RTT are still smoothed
+        let rtts = [100_000, 100_500, 100_500];
+        let expected_rtts = [100., 100.25, 100.375];
+        for (ack, (rtt, expected_rtt)) in rtts.iter().zip(expected_rtts).enumerate() {
+            connection.send_message(1, vec![1, 2, 3]);
+            assert!(!connection.get_packets_to_send().unwrap().is_empty());
+
+            connection.advance_time(Duration::from_micros(*rtt));
+            connection.update_acket_packets(ack as u16, 1);
+            assert!((connection.rtt() - expected_rtt).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn round_time_trip_variance() {
+        let config = ConnectionConfig {
+            rtt_smoothing_factor: 0.5,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+
+        let rtts = [100, 200, 150];
+        let expected_variances = [50., 75., 37.5];
+        for (ack, (rtt, expected_variance)) in rtts.iter().zip(expected_variances).enumerate() {
+            connection.send_message(1, vec![1, 2, 3]);
+            assert!(!connection.get_packets_to_send().unwrap().is_empty());
+
+            connection.advance_time(Duration::from_millis(*rtt));
+            connection.update_acket_packets(ack as u16, 1);
+            assert_eq!(connection.rtt_variance(), expected_variance);
+        }
+
+        // 150ms + 4 * 37.5ms
+        assert_eq!(connection.retransmission_timeout(), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn packet_loss() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        let message: Bytes = vec![1, 2, 3].into();
+        let mut ack_data = AckData { ack: 0, ack_bits: 1 };
+        for i in 0..32 {
+            connection.send_message(1, message.clone());
+            assert!(!connection.get_packets_to_send().unwrap().is_empty());
+
+            // 50% packet loss, the acked packets have a 50ms RTT
+            connection.advance_time(Duration::from_millis(50));
+            if i % 2 == 0 {
+                connection.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+            }
+            connection.advance_time(Duration::from_millis(50));
+
+            ack_data.ack += 1;
+        }
+
+        connection.update_packet_loss();
+        assert_eq!(connection.packet_loss(), 0.5);
+    }
+
+    #[test]
+    fn confirm_only_completed_fragmented_packet() {
+        let config = ConnectionConfig::default();
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let message = vec![7u8; 2500];
+        connection.send_message(0, message.clone());
+
+        let packets = connection.get_packets_to_send().unwrap();
+        assert!(packets.len() > 1);
+        for packet in packets.iter() {
+            assert!(!connection.received_buffer.exists(0));
+            connection.process_packet(packet).unwrap();
+        }
+        // After all fragments are received it should be considered received
+        assert!(connection.received_buffer.exists(0));
+
+        let received_message = connection.receive_message(0).unwrap();
+        assert_eq!(message, received_message);
+    }
+
+    #[test]
+    fn channels_id() {
+        let config = ConnectionConfig {
+            send_channels_config: vec![ReliableChannelConfig::default().into()],
+            ..Default::default()
+        };
+        let connection = RemoteConnection::new(Duration::ZERO, config);
+        assert_eq!(connection.send_channels_id(), vec![0]);
+        assert_eq!(connection.receive_channels_id(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn remove_channel() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(1, vec![0u8; 10]);
+        let packets = connection.get_packets_to_send().unwrap();
+
+        assert!(remote_connection.remove_channel(1));
+        assert!(!remote_connection.remove_channel(1));
+        assert_eq!(remote_connection.send_channels_id(), vec![0, 2]);
+        assert_eq!(remote_connection.receive_channels_id(), vec![0, 2]);
+
+        // Messages in flight for the removed channel are ignored
+        for packet in packets.iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        assert!(remote_connection.is_connected());
+    }
+
+    #[test]
+    fn send_message_invalid_channel() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let error = connection.try_send_message(10, vec![0u8; 10]).unwrap_err();
+        assert!(matches!(error, RechannelError::InvalidChannelId(10)));
+
+        // Should only log the error
+        connection.send_message(10, vec![0u8; 10]);
+        assert!(connection.is_connected());
+    }
+
+    #[test]
+    fn unknown_channel_messages() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut config = ConnectionConfig::default();
+        config
+            .receive_channels_config
+            .retain(|channel_config| channel_config.channel_id() == 0);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+
+        connection.send_message(0, vec![1]);
+        connection.send_message(1, vec![2]);
+        connection.send_message(1, vec![3]);
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+
+        assert!(remote_connection.is_connected());
+        assert_eq!(remote_connection.unknown_channel_messages(), 2);
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![1]);
+        assert!(remote_connection.receive_message(1).is_none());
+    }
+
+    #[test]
+    fn drain_received() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(1, vec![3]);
+        connection.send_message(0, vec![1]);
+        connection.send_message(0, vec![2]);
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+
+        let received: Vec<_> = remote_connection.drain_received().collect();
+        assert_eq!(received, vec![(0, vec![1]), (0, vec![2]), (1, vec![3])]);
+        assert_eq!(remote_connection.drain_received().count(), 0);
+    }
+
+    #[test]
+    fn pause() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.get_packets_to_send().unwrap();
+
+        connection.pause();
+        connection.advance_time(Duration::from_secs(10));
+        assert!(connection.is_paused());
+        assert!(connection.get_packets_to_send().unwrap().is_empty());
+
+        connection.resume();
+        connection.advance_time(Duration::from_millis(50));
+        assert!(connection.get_packets_to_send().unwrap().is_empty());
+
+        connection.advance_time(Duration::from_millis(50));
+        assert_eq!(connection.get_packets_to_send().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn congestion_control() {
+        let config = ConnectionConfig {
+            congestion_control: Some(CongestionConfig {
+                recovery_time: Duration::from_secs(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        assert_eq!(connection.congestion_state(), CongestionState::Good);
+
+        connection.packet_loss = 0.5;
+        connection.update().unwrap();
+        assert_eq!(connection.congestion_state(), CongestionState::Bad);
+
+        connection.packet_loss = 0.0;
+        connection.advance_time(Duration::from_millis(500));
+        connection.update().unwrap();
+        assert_eq!(connection.congestion_state(), CongestionState::Bad);
+
+        connection.advance_time(Duration::from_millis(500));
+        connection.update().unwrap();
+        assert_eq!(connection.congestion_state(), CongestionState::Good);
+    }
+
+    #[test]
+    fn congestion_control_packet_loss() {
+        let config = ConnectionConfig {
+            congestion_control: Some(CongestionConfig {
+                recovery_time: Duration::from_millis(500),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_packets: Vec<Payload> = vec![];
+
+        // Each step the remote acks the packets received in the previous step, 10ms earlier
+        let mut step = |connection: &mut RemoteConnection, remote_connection: &mut RemoteConnection, drop: bool| {
+            connection.advance_time(Duration::from_millis(10));
+            remote_connection.advance_time(Duration::from_millis(10));
+            for packet in remote_packets.drain(..) {
+                connection.process_packet(&packet).unwrap();
+            }
+            connection.update().unwrap();
+
+            connection.send_message(1, vec![0u8; 10]);
+            for packet in connection.get_packets_to_send().unwrap() {
+                if !drop {
+                    remote_connection.process_packet(&packet).unwrap();
+                }
+            }
+            remote_connection.send_message(1, vec![0u8; 10]);
+            remote_packets = remote_connection.get_packets_to_send().unwrap();
+        };
+
+        // No loss is reported on a lossless link, even before the first RTT sample
+        for _ in 0..20 {
+            step(&mut connection, &mut remote_connection, false);
+            assert_eq!(connection.packet_loss(), 0.0);
+            assert_eq!(connection.congestion_state(), CongestionState::Good);
+        }
+        assert!(connection.rtt() > 0.0);
+
+        for _ in 0..5 {
+            step(&mut connection, &mut remote_connection, true);
+        }
+        step(&mut connection, &mut remote_connection, false);
+        assert!(connection.packet_loss() > 0.05);
+        assert_eq!(connection.congestion_state(), CongestionState::Bad);
+
+        // Once the lost packets leave the sample the connection recovers
+        let mut steps = 0;
+        while connection.congestion_state() == CongestionState::Bad {
+            step(&mut connection, &mut remote_connection, false);
+            steps += 1;
+            assert!(steps < 1000, "the congestion state did not recover");
+        }
+    }
+
+    #[test]
+    fn reassembly_stats() {
+        let config = ConnectionConfig {
+            fragment_config: FragmentConfig {
+                reassembly_buffer_size: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+
+        connection.send_message(0, vec![0u8; 2500]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+
+        // Only the first fragment of each packet arrives
+        for _ in 0..6 {
+            connection.send_message(0, vec![0u8; 2500]);
+            let packets = connection.get_packets_to_send().unwrap();
+            remote_connection.process_packet(&packets[0]).unwrap();
+        }
+
+        let stats = remote_connection.reassembly_stats();
+        assert_eq!(stats.completed, 1);
+        assert_eq!(stats.buffered, 4);
+        assert_eq!(stats.evicted, 2);
+    }
+
+    #[test]
+    fn totals() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(0, vec![0u8; 2500]);
+        let packets = connection.get_packets_to_send().unwrap();
+        let bytes: usize = packets.iter().map(|packet| packet.len()).sum();
+        for packet in packets.iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap() {
+            connection.process_packet(&packet).unwrap();
+        }
+
+        let totals = connection.totals();
+        assert_eq!(totals.packets_sent, 3);
+        assert_eq!(totals.bytes_sent, bytes as u64);
+        assert_eq!(totals.packets_received, 1);
+        assert_eq!(totals.packets_acked, 1);
+
+        let remote_totals = remote_connection.totals();
+        assert_eq!(remote_totals.packets_received, 3);
+        assert_eq!(remote_totals.bytes_received, bytes as u64);
+        assert_eq!(remote_totals.packets_sent, 1);
+    }
+
+    #[test]
+    fn packet_type_stats() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(1, vec![0u8; 10]);
+        connection.send_unreliable(vec![1, 2, 3]).unwrap();
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        connection.send_message(1, vec![0u8; 2500]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        connection.advance_time(Duration::from_millis(100));
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+
+        let stats = remote_connection.packet_type_stats();
+        assert_eq!(stats.normal, 1);
+        assert_eq!(stats.datagram, 1);
+        assert_eq!(stats.fragment, 3);
+        assert_eq!(stats.heartbeat, 1);
+    }
+
+    #[test]
+    fn coalesce_stats() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        assert_eq!(connection.coalesce_stats(), CoalesceStats::default());
+
+        for _ in 0..4 {
+            connection.send_message(0, vec![0u8; 95]);
+        }
+        connection.get_packets_to_send().unwrap();
+        connection.send_message(0, vec![0u8; 95]);
+        connection.advance_time(Duration::from_millis(1));
+        connection.get_packets_to_send().unwrap();
+
+        let stats = connection.coalesce_stats();
+        assert_eq!(stats.packets, 2);
+        assert_eq!(stats.messages_per_packet, 2.5);
+        assert!((0.2..0.3).contains(&stats.fill_ratio));
+    }
+
+    #[test]
+    fn coalesce_mtu() {
+        let config = ConnectionConfig {
+            coalesce_mtu: Some(1200),
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        // The messages are coalesced in packets under the coalesce MTU instead of being fragmented
+        for i in 0..30u8 {
+            connection.send_message(1, vec![i; 100]);
+        }
+        let packets = connection.get_packets_to_send().unwrap();
+        assert_eq!(packets.len(), 3);
+        for packet in packets.iter() {
+            assert!(packet.len() <= 1200);
+            remote_connection.process_packet(packet).unwrap();
+        }
+        let mut messages = vec![];
+        while let Some(message) = remote_connection.receive_message(1) {
+            messages.push(message);
+        }
+        messages.sort();
+        assert_eq!(messages, (0..30u8).map(|i| vec![i; 100]).collect::<Vec<_>>());
+        assert_eq!(remote_connection.packet_type_stats().fragment, 0);
+
+        // Messages above the coalesce MTU are sent alone after the coalesced packets
+        connection.send_message(1, vec![7u8; 2000]);
+        connection.send_message(1, vec![1, 2, 3]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        assert_eq!(remote_connection.receive_message(1).unwrap(), vec![1, 2, 3]);
+        assert_eq!(remote_connection.receive_message(1).unwrap().len(), 2000);
+    }
+
+    #[test]
+    fn messages_above_max_packet_size() {
+        let config = ConnectionConfig {
+            max_packet_size: 2000,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+
+        // The messages together are above the max packet size,
+        // the ones that do not fit are sent in the next packets.
+        for i in 0..100u8 {
+            connection.send_message(0, vec![i; 100]);
+        }
+
+        let mut num_packets = 0;
+        loop {
+            let packets = connection.get_packets_to_send().unwrap();
+            if packets.is_empty() {
+                break;
+            }
+            for packet in packets.iter() {
+                remote_connection.process_packet(packet).unwrap();
+            }
+            num_packets += 1;
+        }
+        assert!(num_packets > 1);
+
+        for i in 0..100u8 {
+            assert_eq!(remote_connection.receive_message(0).unwrap(), vec![i; 100]);
+        }
+    }
+
+    #[test]
+    fn for_each_packet_to_send() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(0, vec![1, 2, 3]);
+        connection.send_message(1, vec![0u8; 2500]);
+        let mut packets = vec![];
+        connection.for_each_packet_to_send(|packet| packets.push(packet.to_vec())).unwrap();
+        assert_eq!(packets.len(), 3);
+        for packet in packets.iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![1, 2, 3]);
+        assert_eq!(remote_connection.receive_message(1).unwrap().len(), 2500);
+
+        connection.advance_time(Duration::from_millis(100));
+        let mut heartbeats = 0;
+        connection.for_each_packet_to_send(|_| heartbeats += 1).unwrap();
+        assert_eq!(heartbeats, 1);
+    }
+
+    #[test]
+    fn mtu_discovery() {
+        const PATH_MTU: usize = 900;
+        let config = ConnectionConfig {
+            mtu_discovery: Some(MtuDiscoveryConfig::default()),
+            fragment_config: FragmentConfig {
+                fragment_size: 480,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+
+        for _ in 0..200 {
+            for packet in connection.get_packets_to_send().unwrap() {
+                if packet.len() <= PATH_MTU {
+                    remote_connection.process_packet(&packet).unwrap();
+                }
+            }
+            for packet in remote_connection.get_packets_to_send().unwrap() {
+                connection.process_packet(&packet).unwrap();
+            }
+            connection.advance_time(Duration::from_millis(100));
+            remote_connection.advance_time(Duration::from_millis(100));
+        }
+
+        let mtu = connection.discovered_mtu().unwrap() as usize;
+        assert!((PATH_MTU - 32..=PATH_MTU).contains(&mtu));
+
+        // The fill ratio is relative to the discovered MTU, not the fragment threshold
+        connection.send_message(0, vec![0u8; 420]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        assert!((0.45..0.55).contains(&connection.coalesce_stats().fill_ratio));
+        assert_eq!(remote_connection.receive_message(0).unwrap().len(), 420);
+
+        connection.send_message(0, vec![0u8; 895]);
+        let packets = connection.get_packets_to_send().unwrap();
+        assert_eq!(packets.len(), 2);
+        for packet in packets {
+            assert!(packet.len() <= PATH_MTU);
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        assert_eq!(remote_connection.receive_message(0).unwrap().len(), 895);
+    }
+
+    #[test]
+    fn ping_pong() {
+        let config = ConnectionConfig {
+            ping_interval: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        assert_eq!(connection.last_ping_rtt(), None);
+
+        // Idle connection, the only packets sent are pings and heartbeats
+        connection.advance_time(Duration::from_secs(1));
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+
+        remote_connection.advance_time(Duration::from_millis(50));
+        connection.advance_time(Duration::from_millis(50));
+        for packet in remote_connection.get_packets_to_send().unwrap() {
+            connection.process_packet(&packet).unwrap();
+        }
+
+        assert_eq!(connection.last_ping_rtt(), Some(Duration::from_millis(50)));
+        assert_eq!(connection.rtt(), 50.);
+
+        // Pongs that do not echo the outstanding ping are ignored
+        connection.advance_time(Duration::from_millis(50));
+        for time in [0, 500_000] {
+            let pong = BincodeSerializer.serialize(&Packet::Pong { time }).unwrap();
+            connection.process_packet(&pong).unwrap();
+        }
+        assert_eq!(connection.last_ping_rtt(), Some(Duration::from_millis(50)));
+        assert_eq!(connection.rtt(), 50.);
+    }
+
+    #[test]
+    fn flush_packets() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        // Each message fills most of the packet budget of the reliable channel
+        for _ in 0..4 {
+            connection.send_message(0, vec![0u8; 2500]);
+        }
+        for packet in connection.flush_packets().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+
+        for _ in 0..4 {
+            assert!(remote_connection.receive_message(0).is_some());
+        }
+        assert!(connection.flush_packets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn process_packet_raw() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        // The big message is fragmented, the payload is only returned with the last fragment
+        connection.send_message(0, vec![1, 2, 3]);
+        connection.send_message(1, vec![7u8; 2000]);
+        let packets = connection.get_packets_to_send().unwrap();
+        assert!(packets.len() > 1);
+
+        let mut payloads = vec![];
+        for packet in packets.iter() {
+            if let Some(payload) = remote_connection.process_packet_raw(packet).unwrap() {
+                payloads.push(payload);
+            }
+        }
+        assert_eq!(payloads.len(), 1);
+        assert!(remote_connection.receive_message(0).is_none());
+
+        let channels_packet_data: Vec<ChannelPacketData> = BincodeSerializer.deserialize(&payloads[0]).unwrap();
+        assert_eq!(channels_packet_data.len(), 2);
+        let reliable_data = channels_packet_data.iter().find(|data| data.channel_id == 0).unwrap();
+        assert_eq!(reliable_data.messages.len(), 1);
+
+        // Duplicated packets are still discarded
+        assert!(remote_connection.process_packet_raw(&packets[0]).unwrap().is_none());
+
+        // Normal packets return the payload as received, after the packet header
+        connection.send_message(1, vec![4, 5, 6]);
+        let packet = connection.get_packets_to_send().unwrap().remove(0);
+        let payload = remote_connection.process_packet_raw(&packet).unwrap().unwrap();
+        assert!(packet.ends_with(&payload));
+        let channels_packet_data: Vec<ChannelPacketData> = BincodeSerializer.deserialize(&payload).unwrap();
+        assert_eq!(channels_packet_data[0].messages, vec![vec![4, 5, 6]]);
+    }
+
+    #[test]
+    fn forged_length_prefix() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        // Normal packet with sequence 0, empty acks and an u64::MAX varint length for the channels data
+        let mut packet = vec![0, 0, 0, 0, 253];
+        packet.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            connection.process_packet(&packet),
+            Err(RechannelError::SerializationError(_))
+        ));
+        assert!(connection.is_connected());
+    }
+
+    #[test]
+    fn process_summary() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(0, vec![1, 2, 3]);
+        connection.send_message(0, vec![4, 5, 6]);
+        connection.send_message(1, vec![7, 8, 9]);
+        let packets = connection.get_packets_to_send().unwrap();
+        let summary = remote_connection.process_packet(&packets[0]).unwrap();
+        let mut messages = summary.messages.clone();
+        messages.sort();
+        assert_eq!(messages, vec![(0, 2), (1, 1)]);
+        assert_eq!(summary.num_messages(), 3);
+        assert_eq!(summary.bytes, packets[0].len());
+        assert!(!summary.heartbeat);
+
+        remote_connection.advance_time(Duration::from_millis(100));
+        let packets = remote_connection.get_packets_to_send().unwrap();
+        let summary = connection.process_packet(&packets[0]).unwrap();
+        assert!(summary.heartbeat);
+        assert_eq!(summary.acks, 1);
+        assert_eq!(summary.num_messages(), 0);
+    }
+
+    #[test]
+    fn acks_only_channels_in_packet() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(0, vec![1, 2, 3]);
+        let packets = connection.get_packets_to_send().unwrap();
+        remote_connection.process_packet(&packets[0]).unwrap();
+
+        remote_connection.advance_time(Duration::from_millis(100));
+        let packets = remote_connection.get_packets_to_send().unwrap();
+        connection.process_packet(&packets[0]).unwrap();
+        assert_eq!(connection.acks, vec![(0, 0)]);
+
+        connection.update().unwrap();
+        assert!(connection.acks.is_empty());
+        assert_eq!(connection.delivered_messages().len(), 1);
+    }
+
+    #[test]
+    fn time_since_last_send() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.advance_time(Duration::from_millis(500));
+        assert_eq!(connection.time_since_last_send(), Duration::from_millis(500));
+
+        assert!(!connection.get_packets_to_send().unwrap().is_empty());
+        assert_eq!(connection.time_since_last_send(), Duration::ZERO);
+
+        connection.advance_time(Duration::from_millis(50));
+        assert!(connection.get_packets_to_send().unwrap().is_empty());
+        assert_eq!(connection.time_since_last_send(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn time_until_heartbeat() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.advance_time(Duration::from_millis(30));
+        assert_eq!(connection.time_until_heartbeat(), Duration::from_millis(70));
+
+        connection.advance_time(Duration::from_millis(100));
+        assert_eq!(connection.time_until_heartbeat(), Duration::ZERO);
+
+        connection.get_packets_to_send().unwrap();
+        assert_eq!(connection.time_until_heartbeat(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn has_data_to_send() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        assert!(!connection.has_data_to_send());
+
+        connection.send_message(0, vec![1, 2, 3]);
+        assert!(connection.has_data_to_send());
+        connection.get_packets_to_send().unwrap();
+        assert!(!connection.has_data_to_send());
+
+        // The unacked reliable message is ready again once it's due to be resent
+        connection.advance_time(Duration::from_millis(500));
+        assert!(connection.has_data_to_send());
+        connection.get_packets_to_send().unwrap();
+        assert!(!connection.has_data_to_send());
+
+        connection.send_unreliable(vec![4, 5, 6]).unwrap();
+        assert!(connection.has_data_to_send());
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let config = ConnectionConfig {
+            initial_sequence: u16::MAX - 4,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        assert_eq!(connection.local_sequence(), u16::MAX - 4);
+        exchange_reliable_messages(&mut connection, &mut remote_connection);
+        assert_eq!(connection.local_sequence(), 5);
+    }
+
+    #[test]
+    fn initial_sequence() {
+        for (initial_sequence, remote_initial_sequence) in [(40000, 0), (60000, 40000), (32768, 60000)] {
+            let config = ConnectionConfig {
+                initial_sequence,
+                ..Default::default()
+            };
+            let remote_config = ConnectionConfig {
+                initial_sequence: remote_initial_sequence,
+                ..Default::default()
+            };
+            let mut connection = RemoteConnection::new(Duration::ZERO, config);
+            let mut remote_connection = RemoteConnection::new(Duration::ZERO, remote_config);
+            exchange_reliable_messages(&mut connection, &mut remote_connection);
+            assert_eq!(connection.local_sequence(), initial_sequence.wrapping_add(10));
+        }
+    }
+
+    // Sends 10 reliable messages, each in its own packet, and checks they are all received and acked.
+    fn exchange_reliable_messages(connection: &mut RemoteConnection, remote_connection: &mut RemoteConnection) {
+        for i in 0..10u8 {
+            connection.send_message(0, vec![i]);
+            for packet in connection.get_packets_to_send().unwrap() {
+                remote_connection.process_packet(&packet).unwrap();
+            }
+            remote_connection.advance_time(Duration::from_millis(100));
+            for packet in remote_connection.get_packets_to_send().unwrap() {
+                connection.process_packet(&packet).unwrap();
+            }
+            connection.update().unwrap();
+        }
+
+        for i in 0..10u8 {
+            assert_eq!(remote_connection.receive_message(0).unwrap(), vec![i]);
+        }
+        assert_eq!(connection.pending_messages(0), 0);
+        assert_eq!(connection.totals().packets_acked, 10);
+    }
+
+    #[test]
+    fn send_message_multi() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message_multi(&[0, 1], vec![1, 2, 3]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![1, 2, 3]);
+        assert_eq!(remote_connection.receive_message(1).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn nack_resend() {
+        let config = ConnectionConfig {
+            nack_threshold: Some(3),
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        // The packet with the reliable message is lost
+        connection.send_message(0, vec![1, 2, 3]);
+        connection.get_packets_to_send().unwrap();
+        for _ in 0..3 {
+            connection.send_message(1, vec![0u8; 10]);
+            for packet in connection.get_packets_to_send().unwrap() {
+                remote_connection.process_packet(&packet).unwrap();
+            }
+        }
+
+        connection.advance_time(Duration::from_millis(10));
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap() {
+            connection.process_packet(&packet).unwrap();
+        }
+        connection.update().unwrap();
+
+        // Resent before the message resend time
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn compact_acks() {
+        let config = ConnectionConfig {
+            compact_acks: true,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut default_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        // Fill the ack window
+        for _ in 0..32 {
+            connection.send_message(0, vec![0u8; 10]);
+            for packet in connection.get_packets_to_send().unwrap() {
+                remote_connection.process_packet(&packet).unwrap();
+                default_connection.process_packet(&packet).unwrap();
             }
-            Packet::Fragment {
-                sequence,
-                ack_data,
-                fragment_data,
-            } => {
-                self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+        }
 
-                let packet = self.reassembly_buffer.handle_fragment(
-                    sequence,
-                    fragment_data,
-                    self.config.max_packet_size,
-                    &self.config.fragment_config,
-                )?;
-                match packet {
-                    None => return Ok(()),
-                    Some(packet) => {
-                        // Only consider the packet received when the fragment is completed
-                        self.received_buffer.insert(sequence, ());
-                        packet
-                    }
-                }
+        remote_connection.advance_time(Duration::from_millis(100));
+        default_connection.advance_time(Duration::from_millis(100));
+        let heartbeat = remote_connection.get_packets_to_send().unwrap().remove(0);
+        let default_heartbeat = default_connection.get_packets_to_send().unwrap().remove(0);
+        assert!(heartbeat.len() < default_heartbeat.len());
+
+        connection.process_packet(&heartbeat).unwrap();
+        assert_eq!(connection.packets_in_flight(), 0);
+    }
+
+    #[test]
+    fn delivered_messages() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        let first = connection.try_send_message_with_id(0, vec![1]).unwrap();
+        let second = connection.try_send_message_with_id(0, vec![2]).unwrap();
+        assert_eq!(first, MessageId { channel_id: 0, id: 0 });
+        assert_eq!(second, MessageId { channel_id: 0, id: 1 });
+        assert!(matches!(
+            connection.try_send_message_with_id(1, vec![3]),
+            Err(RechannelError::DeliveryNotTracked(1))
+        ));
+
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        connection.update().unwrap();
+        assert!(connection.delivered_messages().is_empty());
+
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap().iter() {
+            connection.process_packet(packet).unwrap();
+        }
+        connection.update().unwrap();
+        assert_eq!(connection.delivered_messages(), &[first, second]);
+
+        // Only reported in the update the ack was processed
+        connection.update().unwrap();
+        assert!(connection.delivered_messages().is_empty());
+    }
+
+    #[test]
+    fn reset() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        assert!(connection.remove_channel(2));
+        connection.send_message(0, vec![1, 2, 3]);
+        connection.get_packets_to_send().unwrap();
+        connection.disconnect();
+
+        connection.reset();
+        assert!(connection.is_connected());
+        assert_eq!(connection.local_sequence(), 0);
+        assert_eq!(connection.send_channels_id(), vec![0, 1]);
+
+        // The reliable channel starts again from the first message
+        connection.send_message(0, vec![4, 5, 6]);
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![4, 5, 6]);
+        assert!(remote_connection.receive_message(0).is_none());
+    }
+
+    #[test]
+    fn validate_config() {
+        assert_eq!(ConnectionConfig::default().validate(), Ok(()));
+
+        let config = ConnectionConfig {
+            sent_packets_buffer_size: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::BufferSizeNotPowerOfTwo {
+                buffer: "sent packets buffer",
+                size: 100
+            })
+        );
+        assert!(matches!(
+            RemoteConnection::try_new(Duration::ZERO, config),
+            Err(ConfigError::BufferSizeNotPowerOfTwo { size: 100, .. })
+        ));
+
+        let config = ConnectionConfig {
+            send_channels_config: vec![ReliableChannelConfig {
+                sent_packet_buffer_size: 300,
+                ..Default::default()
             }
-            Packet::Heartbeat { ack_data } => {
-                self.update_acket_packets(ack_data.ack, ack_data.ack_bits);
-                return Ok(());
+            .into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::BufferSizeNotPowerOfTwo {
+                buffer: "reliable channel sent packet buffer",
+                size: 300
+            })
+        );
+
+        let mut config = ConnectionConfig {
+            max_packet_size: 1024 * 1024,
+            ..Default::default()
+        };
+        assert!(matches!(config.validate(), Err(ConfigError::TooManyFragments { .. })));
+
+        config.max_packet_size = 512;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::FragmentAboveMaxPacketSize {
+                fragment_above: 1024,
+                max_packet_size: 512
+            })
+        );
+
+        config.max_packet_size = 16 * 1024;
+        config.mtu_discovery = Some(MtuDiscoveryConfig::default());
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::FragmentSizeAboveMtu {
+                fragment_size: 1024,
+                min_mtu: 508
+            })
+        );
+        config.fragment_config.fragment_size = 480;
+        assert_eq!(config.validate(), Ok(()));
+        config.mtu_discovery = None;
+
+        config.max_packet_size = 16 * 1024;
+        config.send_channels_config.push(ChannelConfig::Reliable(Default::default()));
+        assert_eq!(config.validate(), Err(ConfigError::DuplicatedChannelId(0)));
+
+        let config = ConnectionConfig {
+            rtt_smoothing_factor: 1.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidSmoothingFactor {
+                factor: "rtt smoothing factor"
+            })
+        );
+    }
+
+    #[test]
+    fn set_smoothing_factor() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.set_rtt_smoothing_factor(1.0).unwrap();
+        connection.update_rtt(100.);
+        connection.update_rtt(200.);
+        assert_eq!(connection.rtt(), 200.);
+
+        assert!(connection.set_packet_loss_smoothing_factor(-0.1).is_err());
+        assert!(connection.set_rtt_smoothing_factor(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn config_builder() {
+        let config = ConnectionConfig::builder()
+            .max_packet_size(32 * 1024)
+            .heartbeat_time(Duration::from_millis(50))
+            .fragment_above(1000)
+            .build()
+            .unwrap();
+        assert_eq!(config.max_packet_size, 32 * 1024);
+        assert_eq!(config.heartbeat_time, Duration::from_millis(50));
+        assert_eq!(config.fragment_config.fragment_above, 1000);
+        assert_eq!(config.ack_window_size, 32);
+
+        let error = ConnectionConfig::builder().ack_window_size(65).build().unwrap_err();
+        assert_eq!(error, ConfigError::AckWindowTooLarge(65));
+    }
+
+    #[test]
+    fn max_queued_bytes() {
+        let reliable_config = ReliableChannelConfig {
+            max_queued_bytes: Some(100),
+            ..Default::default()
+        };
+        let config = ConnectionConfig {
+            send_channels_config: vec![reliable_config.clone().into()],
+            receive_channels_config: vec![reliable_config.into()],
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+
+        connection.try_send_message(0, vec![0u8; 60]).unwrap();
+        let error = connection.try_send_message(0, vec![0u8; 60]).unwrap_err();
+        assert!(matches!(error, RechannelError::ChannelFull { channel_id: 0 }));
+
+        // Acked messages release the queued bytes
+        for packet in connection.get_packets_to_send().unwrap() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap() {
+            connection.process_packet(&packet).unwrap();
+        }
+        connection.update().unwrap();
+        connection.try_send_message(0, vec![0u8; 60]).unwrap();
+    }
+
+    #[test]
+    fn empty_message_with_ttl() {
+        let reliable_config = ReliableChannelConfig {
+            message_ttl: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+        let config = ConnectionConfig {
+            send_channels_config: vec![reliable_config.into()],
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+
+        let error = connection.try_send_message(0, vec![]).unwrap_err();
+        assert!(matches!(error, RechannelError::EmptyMessage { channel_id: 0 }));
+        connection.try_send_message(0, vec![1]).unwrap();
+    }
+
+    #[test]
+    fn send_message_disconnected() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        connection.disconnect();
+
+        let error = connection.try_send_message(0, vec![0u8; 10]).unwrap_err();
+        assert!(matches!(
+            error,
+            RechannelError::ClientDisconnected(DisconnectionReason::DisconnectedByClient)
+        ));
+    }
+
+    #[test]
+    fn send_message_too_large() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let error = connection.try_send_message(0, vec![0u8; 5000]).unwrap_err();
+        assert!(matches!(
+            error,
+            RechannelError::MessageTooLarge {
+                channel_id: 0,
+                size: 5000,
+                max: 3000
             }
-            Packet::Disconnect { reason } => {
-                self.state = ConnectionState::Disconnected { reason };
-                return Ok(());
+        ));
+
+        // The message was rejected before reaching the channel, so the connection is still valid
+        connection.update().unwrap();
+        assert!(connection.is_connected());
+
+        // Without the result the oversized message still disconnects the connection
+        connection.send_message(0, vec![0u8; 5000]);
+        assert!(matches!(
+            connection.update(),
+            Err(RechannelError::ClientDisconnected(DisconnectionReason::SendChannelError {
+                channel_id: 0,
+                error: ChannelError::SentMessageAboveMaxSize
+            }))
+        ));
+    }
+
+    #[test]
+    fn packets_in_flight() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        for _ in 0..3 {
+            connection.send_message(0, vec![0u8; 10]);
+            let packets = connection.get_packets_to_send().unwrap();
+            for packet in packets.iter() {
+                remote_connection.process_packet(packet).unwrap();
             }
+        }
+        assert_eq!(connection.packets_in_flight(), 3);
+
+        // Heartbeat packets are not in flight
+        connection.advance_time(Duration::from_millis(100));
+        connection.get_packets_to_send().unwrap();
+        assert_eq!(connection.packets_in_flight(), 3);
+
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap().iter() {
+            connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(connection.packets_in_flight(), 0);
+    }
+
+    #[test]
+    fn max_pending_acks() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let config = ConnectionConfig {
+            max_pending_acks: Some(2),
+            ..Default::default()
         };
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
 
-        for channel_packet_data in channels_packet_data.into_iter() {
-            let receive_channel = match self.receive_channels.get_mut(&channel_packet_data.channel_id) {
-                Some(c) => c,
-                None => {
-                    let reason = DisconnectionReason::InvalidChannelId(channel_packet_data.channel_id);
-                    self.state = ConnectionState::Disconnected { reason };
-                    return Err(RechannelError::ClientDisconnected(reason));
-                }
-            };
+        connection.send_message(0, vec![0u8; 10]);
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        assert!(remote_connection.get_packets_to_send().unwrap().is_empty());
 
-            receive_channel.process_messages(channel_packet_data.messages);
+        connection.send_message(0, vec![0u8; 10]);
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
         }
+        let packets = remote_connection.get_packets_to_send().unwrap();
+        assert_eq!(packets.len(), 1);
+        assert!(remote_connection.get_packets_to_send().unwrap().is_empty());
 
-        Ok(())
+        for packet in packets.iter() {
+            connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(connection.packets_in_flight(), 0);
     }
 
-    pub fn get_packets_to_send(&mut self) -> Result<Vec<Payload>, RechannelError> {
-        if let Some(reason) = self.disconnected() {
-            return Err(RechannelError::ClientDisconnected(reason));
+    #[test]
+    fn duplicate_fragments() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        let message = vec![5u8; 2500];
+        connection.send_message(0, message.clone());
+        let packets = connection.get_packets_to_send().unwrap();
+        assert!(packets.len() > 1);
+
+        remote_connection.process_packet(&packets[0]).unwrap();
+        remote_connection.process_packet(&packets[0]).unwrap();
+        assert_eq!(remote_connection.duplicate_fragments(), 1);
+
+        for packet in packets[1..].iter() {
+            remote_connection.process_packet(packet).unwrap();
         }
+        assert_eq!(remote_connection.receive_message(0).unwrap(), message);
+    }
 
-        let sequence = self.sequence;
-        // Aproximated header size for the packet
-        const HEADER_SIZE: u64 = 20;
-        let mut available_bytes = self.config.max_packet_size - HEADER_SIZE;
-        let mut channels_packet_data = vec![];
-        for send_channel in self.send_channels.values_mut() {
-            if let Some(channel_packet_data) = send_channel.get_messages_to_send(available_bytes, sequence, self.current_time) {
-                available_bytes -= bincode::options().serialized_size(&channel_packet_data)?;
-                channels_packet_data.push(channel_packet_data);
+    #[test]
+    fn max_send_bandwidth() {
+        let config = ConnectionConfig {
+            max_send_bandwidth_kbps: Some(80.),
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config);
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        for i in 0..20u8 {
+            connection.send_message(0, vec![i; 1000]);
+        }
+
+        // The initial budget is the max packet size
+        let mut sent_bytes = 0;
+        loop {
+            let packets = connection.get_packets_to_send().unwrap();
+            if packets.is_empty() {
+                break;
+            }
+            for packet in packets.iter() {
+                sent_bytes += packet.len();
+                remote_connection.process_packet(packet).unwrap();
             }
         }
+        assert!(sent_bytes <= 16 * 1024);
+
+        // 80 kbps is 10 bytes per millisecond, the remaining messages are delayed but not lost
+        for _ in 0..20 {
+            connection.advance_time(Duration::from_millis(200));
+            for packet in connection.get_packets_to_send().unwrap().iter() {
+                assert!(packet.len() <= 2000);
+                remote_connection.process_packet(packet).unwrap();
+            }
 
-        if !channels_packet_data.is_empty() {
-            self.sequence = self.sequence.wrapping_add(1);
-            let packet_size = bincode::options().serialized_size(&channels_packet_data)?;
-            let ack_data = self.received_buffer.ack_data();
+            remote_connection.advance_time(Duration::from_millis(200));
+            for packet in remote_connection.get_packets_to_send().unwrap().iter() {
+                connection.process_packet(packet).unwrap();
+            }
+            connection.update().unwrap();
+        }
 
-            let sent_packet = SentPacket::new(self.current_time);
-            self.sent_buffer.insert(sequence, sent_packet);
+        let mut received = vec![];
+        while let Some(message) = remote_connection.receive_message(0) {
+            received.push(message[0]);
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..20).collect::<Vec<u8>>());
+    }
 
-            let packets: Vec<Payload> = if packet_size > self.config.fragment_config.fragment_above {
-                build_fragments(channels_packet_data, sequence, ack_data, &self.config.fragment_config)?
-            } else {
-                let packet = Packet::Normal {
-                    sequence,
-                    ack_data,
-                    channels_packet_data,
-                };
-                let packet = bincode::options().serialize(&packet)?;
-                vec![packet]
-            };
+    #[test]
+    fn unreliable_datagram() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
 
-            self.heartbeat_timer.reset(self.current_time);
-            return Ok(packets);
-        } else if self.heartbeat_timer.is_finished(self.current_time) {
-            let ack_data = self.received_buffer.ack_data();
-            let packet = Packet::Heartbeat { ack_data };
-            let packet = bincode::options().serialize(&packet)?;
+        connection.send_unreliable(vec![1, 2, 3]).unwrap();
+        connection.send_message(0, vec![4, 5, 6]);
+        let error = connection.send_unreliable(vec![0u8; 2000]).unwrap_err();
+        assert!(matches!(error, RechannelError::DatagramTooLarge { size: 2004, max: 1024 }));
+        // The packet header counts towards the threshold
+        let error = connection.send_unreliable(vec![0u8; 1024]).unwrap_err();
+        assert!(matches!(error, RechannelError::DatagramTooLarge { size: 1028, max: 1024 }));
 
-            self.heartbeat_timer.reset(self.current_time);
-            return Ok(vec![packet]);
+        let packets = connection.get_packets_to_send().unwrap();
+        assert_eq!(packets.len(), 2);
+        // Variant index, payload length and payload, the same as a serialized Vec<u8>
+        assert_eq!(packets[0], vec![4, 3, 1, 2, 3]);
+        for packet in packets.iter() {
+            remote_connection.process_packet(packet).unwrap();
         }
 
-        // TODO: should we return Option<Vec>?
-        Ok(vec![])
+        assert_eq!(remote_connection.receive_unreliable().unwrap(), vec![1, 2, 3]);
+        assert!(remote_connection.receive_unreliable().is_none());
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![4, 5, 6]);
     }
 
-    fn update_acket_packets(&mut self, ack: u16, mut ack_bits: u32) {
-        for i in 0..32 {
-            if ack_bits & 1 != 0 {
-                let ack_sequence = ack.wrapping_sub(i);
-                if let Some(ref mut sent_packet) = self.sent_buffer.get_mut(ack_sequence) {
-                    if !sent_packet.ack {
-                        self.acks.push(ack_sequence);
-                        sent_packet.ack = true;
+    #[test]
+    fn max_received_datagrams() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
 
-                        // Update RTT
-                        let rtt = (self.current_time - sent_packet.time).as_secs_f32() * 1000.;
+        for i in 0..MAX_RECEIVED_DATAGRAMS + 10 {
+            connection.send_unreliable((i as u16).to_le_bytes().to_vec()).unwrap();
+        }
+        for packet in connection.get_packets_to_send().unwrap().iter() {
+            remote_connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(remote_connection.dropped_datagrams(), 10);
 
-                        if self.rtt == 0.0 || self.rtt < f32::EPSILON {
-                            self.rtt = rtt;
-                        } else {
-                            self.rtt += (rtt - self.rtt) * self.config.rtt_smoothing_factor;
-                        }
-                    }
-                }
-            }
-            ack_bits >>= 1;
+        // The oldest datagrams were dropped
+        for i in 10..MAX_RECEIVED_DATAGRAMS + 10 {
+            assert_eq!(remote_connection.receive_unreliable().unwrap(), (i as u16).to_le_bytes().to_vec());
         }
+        assert!(remote_connection.receive_unreliable().is_none());
     }
 
-    fn update_packet_loss(&mut self) {
-        let sample_size = self.config.sent_packets_buffer_size;
-        let base_sequence = self.sent_buffer.sequence().wrapping_sub(sample_size as u16);
+    #[test]
+    fn replayed_fragments() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
 
-        let mut packets_dropped = 0;
-        let mut packets_sent = 0;
-        for i in 0..sample_size {
-            if let Some(sent_packet) = self.sent_buffer.get(base_sequence.wrapping_add(i as u16)) {
-                packets_sent += 1;
-                let secs_since_sent = (self.current_time - sent_packet.time).as_secs_f32();
-                if !sent_packet.ack && secs_since_sent > self.rtt * 1.5 {
-                    packets_dropped += 1;
-                }
+        connection.send_message(1, vec![5u8; 2500]);
+        let packets = connection.get_packets_to_send().unwrap();
+        assert!(packets.len() > 1);
+
+        for _ in 0..3 {
+            for packet in packets.iter() {
+                remote_connection.process_packet(packet).unwrap();
             }
         }
 
-        if packets_sent == 0 {
-            return;
-        }
+        assert_eq!(remote_connection.receive_message(1).unwrap(), vec![5u8; 2500]);
+        assert!(remote_connection.receive_message(1).is_none());
+        assert_eq!(remote_connection.duplicate_fragments(), 2 * packets.len() as u64);
+    }
 
-        let packet_loss = packets_dropped as f32 / packets_sent as f32;
-        if self.packet_loss == 0.0 || self.packet_loss < f32::EPSILON {
-            self.packet_loss = packet_loss;
-        } else {
-            self.packet_loss += (packet_loss - self.packet_loss) * self.config.packet_loss_smoothing_factor;
+    #[test]
+    fn duplicate_packets() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(1, vec![1, 2, 3]);
+        let packets = connection.get_packets_to_send().unwrap();
+        assert_eq!(packets.len(), 1);
+
+        remote_connection.process_packet(&packets[0]).unwrap();
+        remote_connection.process_packet(&packets[0]).unwrap();
+        assert_eq!(remote_connection.duplicate_packets(), 1);
+
+        // The unreliable message is only received once
+        assert_eq!(remote_connection.receive_message(1).unwrap(), vec![1, 2, 3]);
+        assert!(remote_connection.receive_message(1).is_none());
+    }
+
+    #[test]
+    fn replayed_packet_older_than_received_buffer() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(1, vec![1, 2, 3]);
+        let replayed_packet = connection.get_packets_to_send().unwrap().remove(0);
+        remote_connection.process_packet(&replayed_packet).unwrap();
+        assert_eq!(remote_connection.receive_message(1).unwrap(), vec![1, 2, 3]);
+
+        let received_packets_buffer_size = ConnectionConfig::default().received_packets_buffer_size;
+        for _ in 0..received_packets_buffer_size + 10 {
+            connection.send_message(1, vec![4, 5, 6]);
+            for packet in connection.get_packets_to_send().unwrap() {
+                remote_connection.process_packet(&packet).unwrap();
+            }
+            while remote_connection.receive_message(1).is_some() {}
         }
+
+        remote_connection.process_packet(&replayed_packet).unwrap();
+        assert_eq!(remote_connection.duplicate_packets(), 1);
+        assert!(remote_connection.receive_message(1).is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::packet::AckData;
+    #[test]
+    fn sequence_and_ack_window() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        for i in 0..3 {
+            connection.send_message(0, vec![0u8; 10]);
+            let packets = connection.get_packets_to_send().unwrap();
+            // Lose the second packet
+            if i != 1 {
+                for packet in packets.iter() {
+                    remote_connection.process_packet(packet).unwrap();
+                }
+            }
+        }
 
-    use super::*;
+        assert_eq!(connection.local_sequence(), 3);
+        assert_eq!(remote_connection.local_sequence(), 0);
+        assert_eq!(remote_connection.remote_ack_window(), (2, 0b101));
+    }
 
     #[test]
-    fn round_time_trip() {
+    fn ack_after_sequence_wrap() {
         let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
 
         let message: Bytes = vec![1, 2, 3].into();
-        let mut ack_data = AckData { ack: 0, ack_bits: 1 };
-        for _ in 0..16 {
+        for sequence in 0..70_000u32 {
             connection.send_message(1, message.clone());
             assert!(!connection.get_packets_to_send().unwrap().is_empty());
 
-            connection.advance_time(Duration::from_millis(100));
-            connection.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+            connection.advance_time(Duration::from_millis(10));
+            connection.update_acket_packets(sequence as u16, 1);
+        }
+        assert_eq!(connection.local_sequence(), (70_000 % 65536) as u16);
+        assert_eq!(connection.rtt(), 10.);
+        assert_eq!(connection.packets_in_flight(), 0);
+
+        connection.send_message(1, message);
+        connection.get_packets_to_send().unwrap();
+        connection.advance_time(Duration::from_millis(10));
+
+        // Stale ack from before the wrap, newer than the last sent packet
+        let stale_ack = connection.local_sequence().wrapping_add(10);
+        connection.update_acket_packets(stale_ack, u64::MAX);
+        assert_eq!(connection.packets_in_flight(), 1);
+    }
 
-            ack_data.ack += 1;
+    #[test]
+    fn ack_window_size() {
+        let config = ConnectionConfig {
+            ack_window_size: 64,
+            ..Default::default()
+        };
+        let mut connection = RemoteConnection::new(Duration::ZERO, config.clone());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, config);
+
+        for _ in 0..50 {
+            connection.send_message(0, vec![0u8; 10]);
+            let packets = connection.get_packets_to_send().unwrap();
+            for packet in packets.iter() {
+                remote_connection.process_packet(packet).unwrap();
+            }
         }
 
-        assert_eq!(connection.rtt(), 100.);
+        let (ack, ack_bits) = remote_connection.remote_ack_window();
+        assert_eq!(ack, 49);
+        assert_eq!(ack_bits, (1 << 50) - 1);
+
+        // Only the latest packet is received, the previous ones are acked by the larger window
+        remote_connection.advance_time(Duration::from_millis(100));
+        for packet in remote_connection.get_packets_to_send().unwrap().iter() {
+            connection.process_packet(packet).unwrap();
+        }
+        assert_eq!(connection.packets_in_flight(), 0);
     }
 
     #[test]
-    fn packet_loss() {
+    fn channel_bandwidth_window() {
         let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
 
-        let message: Bytes = vec![1, 2, 3].into();
-        let mut ack_data = AckData { ack: 0, ack_bits: 1 };
-        for i in 0..32 {
-            connection.send_message(1, message.clone());
-            assert!(!connection.get_packets_to_send().unwrap().is_empty());
+        for i in 0..3 {
+            if i > 0 {
+                connection.advance_time(Duration::from_millis(10));
+                remote_connection.advance_time(Duration::from_millis(10));
+            }
+            connection.send_message(1, vec![0u8; 100]);
+            for packet in connection.get_packets_to_send().unwrap().iter() {
+                remote_connection.process_packet(packet).unwrap();
+            }
+        }
 
-            // 50% packet loss
-            if i % 2 == 0 {
-                connection.update_acket_packets(ack_data.ack, ack_data.ack_bits);
+        // Each channel data has 103 bytes: message count, message length, payload and channel id.
+        // 3 packets sent over 20 ms: 309 * 8 / 20 = 123.6 kbps
+        let sent = connection.channel_bandwidth(1).unwrap();
+        let received = remote_connection.channel_bandwidth(1).unwrap();
+        assert!((sent.sent_kbps - 123.6).abs() < 0.01);
+        assert!((received.received_kbps - 123.6).abs() < 0.01);
+    }
+
+    #[test]
+    fn channel_bandwidth() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        for _ in 0..4 {
+            connection.send_message(0, vec![7u8; 100]);
+            for packet in connection.get_packets_to_send().unwrap() {
+                remote_connection.process_packet(&packet).unwrap();
             }
             connection.advance_time(Duration::from_millis(100));
+            remote_connection.advance_time(Duration::from_millis(100));
+        }
 
-            ack_data.ack += 1;
+        let bandwidth = connection.channel_bandwidth(0).unwrap();
+        assert!(bandwidth.sent_kbps > 0.0);
+        assert_eq!(bandwidth.received_kbps, 0.0);
+
+        let unused_bandwidth = connection.channel_bandwidth(1).unwrap();
+        assert_eq!(unused_bandwidth.sent_kbps, 0.0);
+
+        let remote_bandwidth = remote_connection.channel_bandwidth(0).unwrap();
+        assert!(remote_bandwidth.received_kbps > 0.0);
+
+        assert!(connection.channel_bandwidth(10).is_none());
+    }
+
+    #[test]
+    fn disconnect_with_reason() {
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        let reason = DisconnectionReason::DisconnectedByServer;
+        let packets = connection.disconnect_with_reason(reason).unwrap();
+        assert_eq!(packets.len(), NUM_DISCONNECT_PACKETS_TO_SEND);
+        assert_eq!(connection.disconnected(), Some(reason));
+        assert!(connection.disconnect_with_reason(reason).is_err());
+
+        // Only the last packet arrives
+        remote_connection.process_packet(packets.last().unwrap()).unwrap();
+        assert_eq!(remote_connection.disconnected(), Some(reason));
+    }
+
+    #[derive(Debug)]
+    struct VersionedSerializer {
+        version: u8,
+    }
+
+    impl PacketSerializer for VersionedSerializer {
+        fn serialize<T: ?Sized + serde::Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError> {
+            let mut bytes = vec![self.version];
+            bytes.extend(BincodeSerializer.serialize(value)?);
+            Ok(bytes)
         }
 
-        connection.update_packet_loss();
-        assert_eq!(connection.packet_loss(), 0.5);
+        fn deserialize<T: serde::de::DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError> {
+            match bytes.split_first() {
+                Some((&version, bytes)) if version == self.version => BincodeSerializer.deserialize(bytes),
+                _ => Err("invalid packet version".into()),
+            }
+        }
     }
 
     #[test]
-    fn confirm_only_completed_fragmented_packet() {
-        let config = ConnectionConfig::default();
-        let mut connection = RemoteConnection::new(Duration::ZERO, config);
-        let message = vec![7u8; 2500];
+    fn custom_serializer() {
+        let mut connection =
+            RemoteConnection::with_serializer(Duration::ZERO, ConnectionConfig::default(), VersionedSerializer { version: 1 });
+        let mut remote_connection =
+            RemoteConnection::with_serializer(Duration::ZERO, ConnectionConfig::default(), VersionedSerializer { version: 1 });
+        let mut old_connection =
+            RemoteConnection::with_serializer(Duration::ZERO, ConnectionConfig::default(), VersionedSerializer { version: 0 });
+
+        // Big enough message to be fragmented
+        let message = vec![5u8; 2500];
         connection.send_message(0, message.clone());
-
         let packets = connection.get_packets_to_send().unwrap();
         assert!(packets.len() > 1);
+
         for packet in packets.iter() {
-            assert!(!connection.received_buffer.exists(0));
-            connection.process_packet(packet).unwrap();
+            remote_connection.process_packet(packet).unwrap();
+            assert!(matches!(
+                old_connection.process_packet(packet),
+                Err(RechannelError::SerializationError(_))
+            ));
         }
-        // After all fragments are received it should be considered received
-        assert!(connection.received_buffer.exists(0));
 
-        let received_message = connection.receive_message(0).unwrap();
-        assert_eq!(message, received_message);
+        assert_eq!(remote_connection.receive_message(0).unwrap(), message);
     }
 }
@@ -3,16 +3,22 @@ use crate::packet::AckData;
 #[derive(Debug)]
 pub(crate) struct SequenceBuffer<T> {
     sequence: u16,
+    // Whether an entry was inserted, the first entry is accepted whatever its sequence,
+    // so the sequences don't need to start from 0.
+    started: bool,
     entry_sequences: Box<[Option<u16>]>,
     entries: Box<[Option<T>]>,
 }
 
 impl<T: Clone> SequenceBuffer<T> {
+    /// The size should be a power of two, so the entries stay in order when the sequence wraps around,
+    /// the connection config validates the size of the buffers created from it.
     pub fn with_capacity(size: usize) -> Self {
         assert!(size > 0, "tried to initialize SequenceBuffer with 0 size");
 
         Self {
             sequence: 0,
+            started: false,
             entry_sequences: vec![None; size].into_boxed_slice(),
             entries: vec![None; size].into_boxed_slice(),
         }
@@ -30,7 +36,6 @@ impl<T: Clone> SequenceBuffer<T> {
         None
     }
 
-    #[allow(dead_code)]
     pub fn get(&self, sequence: u16) -> Option<&T> {
         if self.exists(sequence) {
             let index = self.index(sequence);
@@ -67,8 +72,19 @@ impl<T: Clone> SequenceBuffer<T> {
         false
     }
 
+    /// Returns whether the sequence is older than the entries the buffer can hold,
+    /// an entry for it can't be inserted.
+    pub fn is_stale(&self, sequence: u16) -> bool {
+        self.started && sequence_less_than(sequence, self.sequence.wrapping_sub(self.entry_sequences.len() as u16))
+    }
+
     pub fn insert(&mut self, sequence: u16, data: T) -> Option<&mut T> {
-        if sequence_less_than(sequence, self.sequence.wrapping_sub(self.entry_sequences.len() as u16)) {
+        if !self.started {
+            self.started = true;
+            self.sequence = sequence;
+        }
+
+        if self.is_stale(sequence) {
             return None;
         }
 
@@ -111,17 +127,35 @@ impl<T: Clone> SequenceBuffer<T> {
         None
     }
 
+    /// Removes all entries and restarts the sequence, keeping the allocated buffer.
+    pub fn reset(&mut self) {
+        self.sequence = 0;
+        self.started = false;
+        self.entry_sequences.fill(None);
+        self.entries.fill(None);
+    }
+
+    /// Returns the entries that are still in the buffer, from the oldest to the most recent sequence.
+    pub fn entries(&self) -> impl Iterator<Item = &T> {
+        let size = self.size();
+        let base_sequence = self.sequence.wrapping_sub(size as u16);
+        (0..size).filter_map(move |i| self.get(base_sequence.wrapping_add(i as u16)))
+    }
+
     #[inline]
     pub fn sequence(&self) -> u16 {
         self.sequence
     }
 
-    pub fn ack_data(&self) -> AckData {
+    /// Returns the ack data for the most recent sequence,
+    /// acking the previous sequences up to the window size (max 64).
+    pub fn ack_data(&self, ack_window_size: u8) -> AckData {
+        debug_assert!(ack_window_size <= 64);
         let ack = self.sequence().wrapping_sub(1);
         let mut ack_bits = 0;
         let mut mask = 1;
 
-        for i in 0..32 {
+        for i in 0..ack_window_size as u16 {
             let sequence = ack.wrapping_sub(i);
             if self.exists(sequence) {
                 ack_bits |= mask;
@@ -204,6 +238,17 @@ mod tests {
         assert_eq!(count_entries(&buffer), 1);
     }
 
+    #[test]
+    fn reset() {
+        let mut buffer = SequenceBuffer::with_capacity(2);
+        buffer.insert(5, DataStub).unwrap();
+        buffer.reset();
+
+        assert_eq!(buffer.sequence(), 0);
+        assert!(!buffer.exists(5));
+        assert_eq!(count_entries(&buffer), 0);
+    }
+
     #[test]
     fn ack_bits() {
         let mut buffer = SequenceBuffer::with_capacity(64);
@@ -215,10 +260,10 @@ mod tests {
         buffer.insert(7, DataStub).unwrap();
         buffer.insert(30, DataStub).unwrap();
         buffer.insert(31, DataStub).unwrap();
-        let ack_data = buffer.ack_data();
+        let ack_data = buffer.ack_data(32);
 
         assert_eq!(ack_data.ack, 31);
-        assert_eq!(ack_data.ack_bits, 0b11011101000000000000000000000011u32);
+        assert_eq!(ack_data.ack_bits, 0b11011101000000000000000000000011u64);
     }
 
     #[test]
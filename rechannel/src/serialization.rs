@@ -0,0 +1,74 @@
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::error::Error;
+use std::fmt::Debug;
+
+/// Error returned by a [PacketSerializer].
+pub type SerializationError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Serializer used by the [RemoteConnection](crate::remote_connection::RemoteConnection)
+/// to convert the packets to and from bytes.
+pub trait PacketSerializer: Debug {
+    /// Serializes the value into bytes.
+    fn serialize<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError>;
+
+    /// Serializes the value at the end of the buffer, so the buffer can be reused between packets.
+    /// The default implementation serializes the value to a new vector and copies it.
+    fn serialize_into<T: ?Sized + Serialize>(&self, buffer: &mut Vec<u8>, value: &T) -> Result<(), SerializationError> {
+        let bytes = self.serialize(value)?;
+        buffer.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Deserializes the value from bytes.
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError>;
+
+    /// Deserializes the value from the start of the bytes, ignoring the bytes after it,
+    /// and returns it with the number of bytes read. Used to read the packet header without
+    /// deserializing the payload. The default implementation returns None, so the whole packet
+    /// is deserialized instead.
+    fn deserialize_prefix<T: DeserializeOwned + Serialize>(&self, _bytes: &[u8]) -> Result<Option<(T, usize)>, SerializationError> {
+        Ok(None)
+    }
+
+    /// Returns the number of bytes needed to serialize the value.
+    /// The default implementation serializes the value to calculate the size.
+    fn serialized_size<T: ?Sized + Serialize>(&self, value: &T) -> Result<u64, SerializationError> {
+        let bytes = self.serialize(value)?;
+        Ok(bytes.len() as u64)
+    }
+}
+
+/// Default packet serializer, uses bincode with the default options.
+/// Deserialization is limited to the size of the bytes received, so a forged length
+/// prefix fails instead of making bincode read or allocate past the end of the packet.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BincodeSerializer;
+
+impl PacketSerializer for BincodeSerializer {
+    fn serialize<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError> {
+        Ok(bincode::options().serialize(value)?)
+    }
+
+    fn serialize_into<T: ?Sized + Serialize>(&self, buffer: &mut Vec<u8>, value: &T) -> Result<(), SerializationError> {
+        Ok(bincode::options().serialize_into(buffer, value)?)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError> {
+        Ok(bincode::options().with_limit(bytes.len() as u64).deserialize(bytes)?)
+    }
+
+    fn deserialize_prefix<T: DeserializeOwned + Serialize>(&self, bytes: &[u8]) -> Result<Option<(T, usize)>, SerializationError> {
+        let value = bincode::options()
+            .with_limit(bytes.len() as u64)
+            .allow_trailing_bytes()
+            .deserialize(bytes)?;
+        let size = bincode::options().serialized_size(&value)?;
+        Ok(Some((value, size as usize)))
+    }
+
+    fn serialized_size<T: ?Sized + Serialize>(&self, value: &T) -> Result<u64, SerializationError> {
+        Ok(bincode::options().serialized_size(value)?)
+    }
+}
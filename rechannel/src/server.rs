@@ -1,6 +1,8 @@
 use crate::error::{DisconnectionReason, RechannelError};
 use crate::packet::Payload;
-use crate::remote_connection::{ConnectionConfig, RemoteConnection};
+use crate::remote_connection::{
+    ChannelBandwidth, CongestionState, ConnectionConfig, ConnectionTotals, MessageId, ProcessSummary, RemoteConnection,
+};
 use crate::ClientId;
 
 use std::collections::HashMap;
@@ -14,19 +16,21 @@ pub struct RechannelServer<C: ClientId> {
     connections: HashMap<C, RemoteConnection>,
     connection_config: ConnectionConfig,
     disconnections: Vec<(C, DisconnectionReason)>,
+    paused: bool,
 }
 
 impl<C: ClientId> RechannelServer<C> {
     pub fn new(current_time: Duration, connection_config: ConnectionConfig) -> Self {
-        connection_config
-            .fragment_config
-            .assert_can_fragment_packet_with_size(connection_config.max_packet_size);
+        if let Err(e) = connection_config.validate() {
+            panic!("Invalid connection config: {}", e);
+        }
 
         Self {
             current_time,
             connections: HashMap::new(),
             connection_config,
             disconnections: Vec::new(),
+            paused: false,
         }
     }
 
@@ -36,10 +40,34 @@ impl<C: ClientId> RechannelServer<C> {
             return;
         }
 
-        let connection = RemoteConnection::new(self.current_time, self.connection_config.clone());
+        let mut connection = RemoteConnection::new(self.current_time, self.connection_config.clone());
+        if self.paused {
+            connection.pause();
+        }
         self.connections.insert(*connection_id, connection);
     }
 
+    /// Pauses all the connections, including the ones added while paused,
+    /// see [RemoteConnection::pause].
+    pub fn pause(&mut self) {
+        self.paused = true;
+        for connection in self.connections.values_mut() {
+            connection.pause();
+        }
+    }
+
+    /// Resumes all the connections from where they were paused.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        for connection in self.connections.values_mut() {
+            connection.resume();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     /// Returns whether or not the server has connections
     pub fn has_connections(&self) -> bool {
         !self.connections.is_empty()
@@ -56,6 +84,13 @@ impl<C: ClientId> RechannelServer<C> {
         }
     }
 
+    pub fn client_rtt_variance(&self, connection_id: C) -> f32 {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.rtt_variance(),
+            None => 0.0,
+        }
+    }
+
     pub fn client_packet_loss(&self, connection_id: C) -> f32 {
         match self.connections.get(&connection_id) {
             Some(connection) => connection.packet_loss(),
@@ -63,15 +98,60 @@ impl<C: ClientId> RechannelServer<C> {
         }
     }
 
+    pub fn client_last_ping_rtt(&self, connection_id: C) -> Option<Duration> {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.last_ping_rtt(),
+            None => None,
+        }
+    }
+
+    pub fn client_totals(&self, connection_id: C) -> Option<ConnectionTotals> {
+        self.connections.get(&connection_id).map(|connection| connection.totals())
+    }
+
+    pub fn client_discovered_mtu(&self, connection_id: C) -> Option<u64> {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.discovered_mtu(),
+            None => None,
+        }
+    }
+
+    pub fn client_congestion_state(&self, connection_id: C) -> CongestionState {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.congestion_state(),
+            None => CongestionState::Good,
+        }
+    }
+
+    pub fn client_packets_in_flight(&self, connection_id: C) -> usize {
+        match self.connections.get(&connection_id) {
+            Some(connection) => connection.packets_in_flight(),
+            None => 0,
+        }
+    }
+
+    /// Returns the bandwidth used by the client's channel.
+    /// Returns None if the client or the channel does not exist.
+    pub fn client_channel_bandwidth<I: Into<u8>>(&self, connection_id: C, channel_id: I) -> Option<ChannelBandwidth> {
+        self.connections
+            .get(&connection_id)
+            .and_then(|connection| connection.channel_bandwidth(channel_id))
+    }
+
     /// Similar to disconnect but does not emit an event
     pub fn remove_connection(&mut self, connection_id: &C) {
         self.connections.remove(connection_id);
     }
 
     pub fn disconnect(&mut self, connection_id: &C) {
+        self.disconnect_with_reason(connection_id, DisconnectionReason::DisconnectedByServer);
+    }
+
+    /// Disconnects the connection, the reason is emitted with the disconnection event
+    /// and should be sent to the remote connection.
+    pub fn disconnect_with_reason(&mut self, connection_id: &C, reason: DisconnectionReason) {
         if self.connections.remove(connection_id).is_some() {
-            self.disconnections
-                .push((*connection_id, DisconnectionReason::DisconnectedByServer));
+            self.disconnections.push((*connection_id, reason));
         }
     }
 
@@ -108,6 +188,27 @@ impl<C: ClientId> RechannelServer<C> {
         }
     }
 
+    pub fn has_data_to_send(&self, connection_id: &C) -> bool {
+        match self.connections.get(connection_id) {
+            Some(connection) => connection.has_data_to_send(),
+            None => false,
+        }
+    }
+
+    pub fn pending_messages<I: Into<u8>>(&self, connection_id: &C, channel_id: I) -> usize {
+        match self.connections.get(connection_id) {
+            Some(connection) => connection.pending_messages(channel_id),
+            None => 0,
+        }
+    }
+
+    pub fn pending_bytes<I: Into<u8>>(&self, connection_id: &C, channel_id: I) -> usize {
+        match self.connections.get(connection_id) {
+            Some(connection) => connection.pending_bytes(channel_id),
+            None => 0,
+        }
+    }
+
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, connection_id: &C, channel_id: I, message: B) {
         match self.connections.get_mut(connection_id) {
             Some(connection) => connection.send_message(channel_id, message),
@@ -115,6 +216,64 @@ impl<C: ClientId> RechannelServer<C> {
         }
     }
 
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        connection_id: &C,
+        channel_id: I,
+        message: B,
+    ) -> Result<(), RechannelError> {
+        match self.connections.get_mut(connection_id) {
+            Some(connection) => connection.try_send_message(channel_id, message),
+            None => Err(RechannelError::ClientNotFound),
+        }
+    }
+
+    pub fn try_send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        connection_id: &C,
+        channel_id: I,
+        message: B,
+        priority: u8,
+    ) -> Result<(), RechannelError> {
+        match self.connections.get_mut(connection_id) {
+            Some(connection) => connection.try_send_message_with_priority(channel_id, message, priority),
+            None => Err(RechannelError::ClientNotFound),
+        }
+    }
+
+    pub fn drain_received(&mut self, connection_id: &C) -> impl Iterator<Item = (u8, Payload)> + '_ {
+        self.connections
+            .get_mut(connection_id)
+            .into_iter()
+            .flat_map(|connection| connection.drain_received())
+    }
+
+    pub fn try_send_message_with_id<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        connection_id: &C,
+        channel_id: I,
+        message: B,
+    ) -> Result<MessageId, RechannelError> {
+        match self.connections.get_mut(connection_id) {
+            Some(connection) => connection.try_send_message_with_id(channel_id, message),
+            None => Err(RechannelError::ClientNotFound),
+        }
+    }
+
+    pub fn delivered_messages(&self, connection_id: &C) -> &[MessageId] {
+        match self.connections.get(connection_id) {
+            Some(connection) => connection.delivered_messages(),
+            None => &[],
+        }
+    }
+
+    pub fn expired_messages(&self, connection_id: &C) -> &[MessageId] {
+        match self.connections.get(connection_id) {
+            Some(connection) => connection.expired_messages(),
+            None => &[],
+        }
+    }
+
     pub fn receive_message<I: Into<u8>>(&mut self, connection_id: &C, channel_id: I) -> Option<Payload> {
         if let Some(connection) = self.connections.get_mut(connection_id) {
             return connection.receive_message(channel_id);
@@ -122,6 +281,20 @@ impl<C: ClientId> RechannelServer<C> {
         None
     }
 
+    pub fn send_unreliable<B: Into<Bytes>>(&mut self, connection_id: &C, payload: B) -> Result<(), RechannelError> {
+        match self.connections.get_mut(connection_id) {
+            Some(connection) => connection.send_unreliable(payload),
+            None => Err(RechannelError::ClientNotFound),
+        }
+    }
+
+    pub fn receive_unreliable(&mut self, connection_id: &C) -> Option<Payload> {
+        if let Some(connection) = self.connections.get_mut(connection_id) {
+            return connection.receive_unreliable();
+        }
+        None
+    }
+
     pub fn connections_id(&self) -> Vec<C> {
         self.connections.keys().copied().collect()
     }
@@ -148,7 +321,21 @@ impl<C: ClientId> RechannelServer<C> {
         }
     }
 
-    pub fn process_packet_from(&mut self, payload: &[u8], connection_id: &C) -> Result<(), RechannelError> {
+    pub fn for_each_packet_to_send<F: FnMut(&[u8])>(&mut self, connection_id: &C, send: F) -> Result<(), RechannelError> {
+        match self.connections.get_mut(connection_id) {
+            Some(connection) => connection.for_each_packet_to_send(send),
+            None => Err(RechannelError::ClientNotFound),
+        }
+    }
+
+    pub fn flush_packets(&mut self, connection_id: &C) -> Result<Vec<Payload>, RechannelError> {
+        match self.connections.get_mut(connection_id) {
+            Some(connection) => connection.flush_packets(),
+            None => Err(RechannelError::ClientNotFound),
+        }
+    }
+
+    pub fn process_packet_from(&mut self, payload: &[u8], connection_id: &C) -> Result<ProcessSummary, RechannelError> {
         match self.connections.get_mut(connection_id) {
             Some(connection) => connection.process_packet(payload),
             None => Err(RechannelError::ClientNotFound),
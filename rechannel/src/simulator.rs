@@ -0,0 +1,140 @@
+use crate::packet::Payload;
+
+use rand::Rng;
+
+use std::time::Duration;
+
+/// Configuration for the network conditions simulated by the [NetworkSimulator].
+#[derive(Debug, Clone)]
+pub struct NetworkSimulatorConfig {
+    /// Delay added to every packet.
+    pub latency: Duration,
+    /// Random variation added or subtracted from the latency of each packet.
+    pub jitter: Duration,
+    /// Chance of a packet being dropped.
+    /// Values between 0.0 and 1.0.
+    pub packet_loss: f32,
+    /// Chance of a packet being duplicated.
+    /// Values between 0.0 and 1.0.
+    pub duplicate: f32,
+}
+
+#[derive(Debug)]
+struct DelayedPacket {
+    delivery_time: Duration,
+    payload: Payload,
+}
+
+/// Simulates latency, jitter, packet loss and duplication for the packets going through it.
+/// Packets generated by a connection are sent to the simulator, and the packets
+/// received from the simulator should be processed by the remote connection.
+#[derive(Debug)]
+pub struct NetworkSimulator {
+    config: NetworkSimulatorConfig,
+    current_time: Duration,
+    packets: Vec<DelayedPacket>,
+}
+
+impl Default for NetworkSimulatorConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            packet_loss: 0.0,
+            duplicate: 0.0,
+        }
+    }
+}
+
+impl NetworkSimulator {
+    pub fn new(config: NetworkSimulatorConfig) -> Self {
+        Self {
+            config,
+            current_time: Duration::ZERO,
+            packets: vec![],
+        }
+    }
+
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.current_time += duration;
+    }
+
+    /// Sends a packet through the simulated network.
+    pub fn send_packet(&mut self, payload: Payload) {
+        let mut rng = rand::thread_rng();
+        if rng.gen::<f32>() < self.config.packet_loss {
+            return;
+        }
+
+        if rng.gen::<f32>() < self.config.duplicate {
+            self.delay_packet(payload.clone());
+        }
+
+        self.delay_packet(payload);
+    }
+
+    /// Returns the packets that have arrived, ordered by their arrival time.
+    pub fn receive_packets(&mut self) -> Vec<Payload> {
+        self.packets.sort_by_key(|packet| packet.delivery_time);
+        let arrived = self.packets.partition_point(|packet| packet.delivery_time <= self.current_time);
+
+        self.packets.drain(..arrived).map(|packet| packet.payload).collect()
+    }
+
+    fn delay_packet(&mut self, payload: Payload) {
+        // Random jitter between -jitter and +jitter
+        let jitter = self.config.jitter.mul_f32(rand::thread_rng().gen::<f32>() * 2.);
+        let delivery_time = (self.current_time + self.config.latency + jitter).saturating_sub(self.config.jitter);
+        let delivery_time = delivery_time.max(self.current_time);
+
+        self.packets.push(DelayedPacket { delivery_time, payload });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote_connection::{ConnectionConfig, RemoteConnection};
+
+    #[test]
+    fn latency() {
+        let config = NetworkSimulatorConfig {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let mut simulator = NetworkSimulator::new(config);
+        let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+        let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+
+        connection.send_message(0, vec![1, 2, 3]);
+        for packet in connection.get_packets_to_send().unwrap() {
+            simulator.send_packet(packet);
+        }
+
+        simulator.advance_time(Duration::from_millis(50));
+        assert!(simulator.receive_packets().is_empty());
+
+        simulator.advance_time(Duration::from_millis(50));
+        for packet in simulator.receive_packets() {
+            remote_connection.process_packet(&packet).unwrap();
+        }
+        assert_eq!(remote_connection.receive_message(0).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn packet_loss_and_duplicate() {
+        let mut simulator = NetworkSimulator::new(NetworkSimulatorConfig {
+            packet_loss: 1.0,
+            ..Default::default()
+        });
+        simulator.send_packet(vec![0u8; 10]);
+        assert!(simulator.receive_packets().is_empty());
+
+        let mut simulator = NetworkSimulator::new(NetworkSimulatorConfig {
+            duplicate: 1.0,
+            ..Default::default()
+        });
+        simulator.send_packet(vec![0u8; 10]);
+        assert_eq!(simulator.receive_packets().len(), 2);
+    }
+}
@@ -5,6 +5,7 @@ pub struct Timer {
     duration: Duration,
     start_time: Duration,
     force_finish: bool,
+    paused_time: Option<Duration>,
 }
 
 impl Timer {
@@ -13,19 +14,74 @@ impl Timer {
             duration,
             start_time,
             force_finish: false,
+            paused_time: None,
         }
     }
 
     pub fn reset(&mut self, current_time: Duration) {
         self.start_time = current_time;
         self.force_finish = false;
+        if self.paused_time.is_some() {
+            self.paused_time = Some(current_time);
+        }
+    }
+
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
     }
 
     pub fn finish(&mut self) {
         self.force_finish = true;
     }
 
+    /// Stops the elapsed time accounting until the timer is resumed.
+    pub fn pause(&mut self, current_time: Duration) {
+        if self.paused_time.is_none() {
+            self.paused_time = Some(current_time);
+        }
+    }
+
+    /// Resumes the timer from the elapsed time it had when it was paused.
+    pub fn resume(&mut self, current_time: Duration) {
+        if let Some(paused_time) = self.paused_time.take() {
+            self.start_time += current_time.saturating_sub(paused_time);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_time.is_some()
+    }
+
+    /// Returns the time left before the timer finishes, zero when it's finished.
+    pub fn remaining(&self, current_time: Duration) -> Duration {
+        if self.force_finish {
+            return Duration::ZERO;
+        }
+
+        let current_time = self.paused_time.unwrap_or(current_time);
+        self.duration.saturating_sub(current_time.saturating_sub(self.start_time))
+    }
+
     pub fn is_finished(&self, current_time: Duration) -> bool {
+        let current_time = self.paused_time.unwrap_or(current_time);
         self.force_finish || (current_time - self.start_time >= self.duration)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pause_and_resume() {
+        let mut timer = Timer::new(Duration::ZERO, Duration::from_secs(2));
+        timer.pause(Duration::from_secs(1));
+        assert!(timer.is_paused());
+        assert!(!timer.is_finished(Duration::from_secs(10)));
+
+        timer.resume(Duration::from_secs(10));
+        assert!(!timer.is_paused());
+        assert!(!timer.is_finished(Duration::from_millis(10500)));
+        assert!(timer.is_finished(Duration::from_secs(11)));
+    }
+}
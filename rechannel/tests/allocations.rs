@@ -0,0 +1,69 @@
+//! Runs in its own test binary, the global allocator counts the allocations of every test in it.
+use bytes::Bytes;
+use rechannel::remote_connection::{ConnectionConfig, RemoteConnection};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::Duration;
+
+// Counts the bytes allocated by each thread, so tests running in parallel don't interfere
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.with(|allocated| allocated.set(allocated.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocated_bytes() -> usize {
+    ALLOCATED_BYTES.with(|allocated| allocated.get())
+}
+
+#[test]
+fn fragments_copy_the_payload_once() {
+    // Maximum message size of the default reliable channel, sent in 3 fragments
+    const MESSAGE_SIZE: usize = 3000;
+    let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+    connection.send_message(0, Bytes::from(vec![7u8; MESSAGE_SIZE]));
+
+    let allocated = allocated_bytes();
+    let mut sent_bytes = 0;
+    connection.for_each_packet_to_send(|packet| sent_bytes += packet.len()).unwrap();
+    let allocated = allocated_bytes() - allocated;
+
+    // The message is serialized with the channel data, then into the packet payload,
+    // and the fragments are serialized from slices of the payload.
+    // Copying each fragment payload before serializing it would allocate a fourth copy.
+    assert!(sent_bytes > MESSAGE_SIZE);
+    assert!(allocated < MESSAGE_SIZE * 3 + MESSAGE_SIZE / 2, "allocated {} bytes", allocated);
+}
+
+#[test]
+fn raw_normal_packets_are_not_deserialized() {
+    let mut connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+    let mut remote_connection = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+    for _ in 0..50 {
+        connection.send_message(1, Bytes::from(vec![7u8; 10]));
+    }
+    let packets = connection.get_packets_to_send().unwrap();
+    assert_eq!(packets.len(), 1);
+
+    let allocated = allocated_bytes();
+    let payload = remote_connection.process_packet_raw(&packets[0]).unwrap().unwrap();
+    let allocated = allocated_bytes() - allocated;
+
+    // The payload is copied from the packet, deserializing it would allocate each message
+    assert!(allocated < payload.len() * 2, "allocated {} bytes", allocated);
+}
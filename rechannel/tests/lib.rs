@@ -1,7 +1,7 @@
 use bytes::Bytes;
 use rechannel::{
     disconnect_packet,
-    error::DisconnectionReason,
+    error::{DisconnectionReason, RechannelError},
     remote_connection::{ConnectionConfig, RemoteConnection},
     server::RechannelServer,
 };
@@ -115,6 +115,29 @@ fn test_server_disconnect_client() {
     assert_eq!(reason, client_reason);
 }
 
+#[test]
+fn test_server_disconnect_with_custom_reason() {
+    init_log();
+    let mut server = RechannelServer::new(Duration::ZERO, ConnectionConfig::default());
+    let mut client = RemoteConnection::new(Duration::ZERO, ConnectionConfig::default());
+    let client_id = 0u64;
+    server.add_connection(&client_id);
+
+    server.disconnect_with_reason(&client_id, DisconnectionReason::Custom(3));
+
+    let (_, reason) = server.disconnected_client().unwrap();
+    assert_eq!(reason, DisconnectionReason::Custom(3));
+
+    let packet = disconnect_packet(reason).unwrap();
+    client.process_packet(&packet).unwrap();
+
+    assert_eq!(client.disconnected(), Some(DisconnectionReason::Custom(3)));
+    assert!(matches!(
+        client.update(),
+        Err(RechannelError::ClientDisconnected(DisconnectionReason::Custom(3)))
+    ));
+}
+
 #[test]
 fn test_client_disconnect() {
     init_log();
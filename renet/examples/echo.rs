@@ -85,8 +85,8 @@ fn server(addr: SocketAddr) {
                     usernames.insert(id, username.0);
                     println!("Client {} connected.", id)
                 }
-                ServerEvent::ClientDisconnected(id) => {
-                    println!("Client {} disconnected", id);
+                ServerEvent::ClientDisconnected(id, reason) => {
+                    println!("Client {} disconnected: {}", id, reason);
                     usernames.remove_entry(&id);
                 }
             }
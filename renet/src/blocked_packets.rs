@@ -0,0 +1,88 @@
+use crate::transport::Transport;
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+
+/// Maximum number of packets kept while the socket send buffer is full,
+/// packets above it are dropped and reliable messages are resent later by the channels.
+const MAX_BLOCKED_PACKETS: usize = 256;
+
+/// Packets that could not be sent because the transport would block.
+/// They are sent again, in order, before any new packet.
+#[derive(Debug, Default)]
+pub(crate) struct BlockedPackets {
+    packets: VecDeque<(SocketAddr, Vec<u8>)>,
+}
+
+impl BlockedPackets {
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Sends the packet, queueing it when the transport would block or other packets are still queued.
+    pub fn send_to<T: Transport>(&mut self, transport: &mut T, packet: &[u8], addr: SocketAddr) -> Result<(), io::Error> {
+        self.resend(transport)?;
+        if self.packets.is_empty() {
+            match transport.send(packet, addr) {
+                Ok(_) => return Ok(()),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.push(packet, addr);
+        Ok(())
+    }
+
+    /// Sends the queued packets until the transport would block again.
+    pub fn resend<T: Transport>(&mut self, transport: &mut T) -> Result<(), io::Error> {
+        while let Some((addr, packet)) = self.packets.front() {
+            match transport.send(packet, *addr) {
+                Ok(_) => {
+                    self.packets.pop_front();
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, packet: &[u8], addr: SocketAddr) {
+        if self.packets.len() >= MAX_BLOCKED_PACKETS {
+            log::warn!("Dropped packet to {}, too many packets waiting for the socket", addr);
+            return;
+        }
+
+        self.packets.push_back((addr, packet.to_vec()));
+    }
+}
+
+#[cfg(all(test, feature = "udp"))]
+mod tests {
+    use super::*;
+
+    use std::net::UdpSocket;
+
+    #[test]
+    fn max_blocked_packets() {
+        let mut socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let remote_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let remote_addr = remote_socket.local_addr().unwrap();
+
+        let mut blocked_packets = BlockedPackets::default();
+        for _ in 0..MAX_BLOCKED_PACKETS + 10 {
+            blocked_packets.push(&[0u8; 10], remote_addr);
+        }
+        assert_eq!(blocked_packets.len(), MAX_BLOCKED_PACKETS);
+
+        blocked_packets.resend(&mut socket).unwrap();
+        assert_eq!(blocked_packets.len(), 0);
+
+        let mut buffer = [0u8; 10];
+        let (len, _) = remote_socket.recv_from(&mut buffer).unwrap();
+        assert_eq!(len, 10);
+    }
+}
@@ -1,11 +1,17 @@
 use crate::{
+    blocked_packets::BlockedPackets,
     error::{DisconnectionReason, RenetError},
     network_info::{ClientPacketInfo, NetworkInfo, PacketInfo},
+    transport::Transport,
     RenetConnectionConfig,
 };
 
 use log::debug;
-use rechannel::{error::RechannelError, remote_connection::RemoteConnection, Bytes};
+use rechannel::{
+    error::RechannelError,
+    remote_connection::{CongestionState, ConnectionTotals, MessageId, RemoteConnection},
+    Bytes,
+};
 use renetcode::{ConnectToken, NetcodeClient, NetcodeError, NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
 
 use std::net::UdpSocket;
@@ -30,19 +36,37 @@ pub enum ClientAuthentication {
     },
 }
 
+/// Current state of the connection between the client and the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The client is establishing the connection with the server.
+    Connecting,
+    /// The client is connected and can exchange messages with the server.
+    Connected,
+    /// The client was disconnected, messages can no longer be sent.
+    Disconnected(DisconnectionReason),
+}
+
 /// A client that establishes an authenticated connection with a server.
 /// Can send/receive encrypted messages from/to the server.
+/// The packets are sent with an [UdpSocket] by default, or any other [Transport].
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
-pub struct RenetClient {
+pub struct RenetClient<T = UdpSocket> {
     current_time: Duration,
     netcode_client: NetcodeClient,
-    socket: UdpSocket,
+    transport: T,
     reliable_connection: RemoteConnection,
-    buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    buffer: Box<[u8]>,
     client_packet_info: ClientPacketInfo,
+    blocked_packets: BlockedPackets,
+    discarded_packets: u64,
 }
 
+#[cfg(feature = "udp")]
 impl RenetClient {
+    /// Creates a new client, the socket should be bound to an address from the same
+    /// IP version as the server address. To use an IPv6 dual-stack socket to connect
+    /// to an IPv4 server, use the IPv4-mapped IPv6 server address (::ffff:a.b.c.d).
     pub fn new(
         current_time: Duration,
         socket: UdpSocket,
@@ -50,6 +74,45 @@ impl RenetClient {
         authentication: ClientAuthentication,
     ) -> Result<Self, RenetError> {
         socket.set_nonblocking(true)?;
+        let local_addr = socket.local_addr()?;
+        let client = Self::with_transport(current_time, socket, config, authentication)?;
+        let server_addr = client.netcode_client.server_addr();
+        if local_addr.is_ipv4() != server_addr.is_ipv4() {
+            return Err(RenetError::AddressFamilyMismatch { local_addr, server_addr });
+        }
+
+        Ok(client)
+    }
+
+    #[doc(hidden)]
+    pub fn __test() -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = "127.0.0.1:5000".parse().unwrap();
+
+        Self::new(
+            Duration::ZERO,
+            socket,
+            Default::default(),
+            ClientAuthentication::Unsecure {
+                client_id: 0,
+                server_addr,
+                user_data: None,
+                protocol_id: 0,
+            },
+        )
+        .unwrap()
+    }
+}
+
+impl<T: Transport> RenetClient<T> {
+    /// Creates a client that sends and receives the packets with the transport,
+    /// the transport should not block when there are no packets to receive.
+    pub fn with_transport(
+        current_time: Duration,
+        transport: T,
+        config: RenetConnectionConfig,
+        authentication: ClientAuthentication,
+    ) -> Result<Self, RenetError> {
         let reliable_connection = RemoteConnection::new(current_time, config.to_connection_config());
         let connect_token: ConnectToken = match authentication {
             ClientAuthentication::Unsecure {
@@ -75,37 +138,38 @@ impl RenetClient {
 
         Ok(Self {
             current_time,
-            buffer: [0u8; NETCODE_MAX_PACKET_BYTES],
-            socket,
+            buffer: vec![0u8; NETCODE_MAX_PACKET_BYTES].into_boxed_slice(),
+            transport,
             reliable_connection,
             netcode_client,
             client_packet_info,
+            blocked_packets: BlockedPackets::default(),
+            discarded_packets: 0,
         })
     }
 
-    #[doc(hidden)]
-    pub fn __test() -> Self {
-        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
-        let server_addr = "127.0.0.1:5000".parse().unwrap();
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
 
-        Self::new(
-            Duration::ZERO,
-            socket,
-            Default::default(),
-            ClientAuthentication::Unsecure {
-                client_id: 0,
-                server_addr,
-                user_data: None,
-                protocol_id: 0,
-            },
-        )
-        .unwrap()
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
     }
 
     pub fn client_id(&self) -> u64 {
         self.netcode_client.client_id()
     }
 
+    /// Returns the index of the client slot in the server, None if not connected.
+    pub fn client_index(&self) -> Option<u32> {
+        self.netcode_client.client_index()
+    }
+
+    /// Returns the maximum number of clients of the server, None if not connected.
+    pub fn max_clients(&self) -> Option<u32> {
+        self.netcode_client.max_clients()
+    }
+
     pub fn is_connecting(&self) -> bool {
         self.netcode_client.is_connecting()
     }
@@ -114,6 +178,12 @@ impl RenetClient {
         self.netcode_client.is_connected()
     }
 
+    /// Returns the number of packets discarded because they were received from an address
+    /// that is not the server address.
+    pub fn discarded_packets(&self) -> u64 {
+        self.discarded_packets
+    }
+
     /// If the client is disconnected, returns the reason.
     pub fn disconnected(&self) -> Option<DisconnectionReason> {
         if let Some(reason) = self.reliable_connection.disconnected() {
@@ -127,11 +197,48 @@ impl RenetClient {
         None
     }
 
+    /// Returns the current state of the connection.
+    pub fn state(&self) -> ConnectionState {
+        if let Some(reason) = self.disconnected() {
+            return ConnectionState::Disconnected(reason);
+        }
+
+        if self.netcode_client.is_connected() {
+            ConnectionState::Connected
+        } else {
+            ConnectionState::Connecting
+        }
+    }
+
+    /// Pauses the client, for example when the application is suspended. The time passed while
+    /// paused is ignored, so the connection does not time out and no heartbeats or resends are sent.
+    pub fn pause(&mut self) {
+        self.netcode_client.pause();
+        self.reliable_connection.pause();
+    }
+
+    /// Resumes the client from where it was paused.
+    pub fn resume(&mut self) {
+        self.netcode_client.resume();
+        self.reliable_connection.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.netcode_client.is_paused()
+    }
+
     /// Disconnect the client from the server.
     pub fn disconnect(&mut self) {
         match self.netcode_client.disconnect() {
             Ok((addr, payload)) => {
-                if let Err(e) = send_to(self.current_time, &self.socket, &mut self.client_packet_info, payload, addr) {
+                if let Err(e) = send_to(
+                    self.current_time,
+                    &mut self.transport,
+                    &mut self.blocked_packets,
+                    &mut self.client_packet_info,
+                    payload,
+                    addr,
+                ) {
                     log::error!("failed to send disconnect packet to server: {}", e);
                 }
             }
@@ -144,37 +251,252 @@ impl RenetClient {
         self.reliable_connection.receive_message(channel_id)
     }
 
-    /// Send a message to the server over a channel.
+    /// Send a message to the server over a channel, logs an error if the client is disconnected.
+    /// A message above the channel max message size disconnects the client.
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        if let Some(reason) = self.netcode_client.disconnected() {
+            log::error!("Failed to send message: {}", NetcodeError::Disconnected(reason));
+            return;
+        }
         self.reliable_connection.send_message(channel_id, message);
     }
 
+    /// Send a message to the server over a channel, returns an error if the channel does not exist
+    /// or if the client is disconnected. Messages sent while connecting are queued.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), RenetError> {
+        if let Some(reason) = self.netcode_client.disconnected() {
+            return Err(NetcodeError::Disconnected(reason).into());
+        }
+        self.reliable_connection.try_send_message(channel_id, message)?;
+        Ok(())
+    }
+
+    /// Send a message to the server over a channel with a priority, higher priority messages
+    /// are sent first when they do not fit in the same packet. Only reliable channels use the priority.
+    pub fn try_send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        channel_id: I,
+        message: B,
+        priority: u8,
+    ) -> Result<(), RenetError> {
+        if let Some(reason) = self.netcode_client.disconnected() {
+            return Err(NetcodeError::Disconnected(reason).into());
+        }
+        self.reliable_connection
+            .try_send_message_with_priority(channel_id, message, priority)?;
+        Ok(())
+    }
+
+    /// Send the same message to the server over multiple channels, without copying the message.
+    pub fn send_message_multi<B: Into<Bytes>>(&mut self, channel_ids: &[u8], message: B) {
+        let message = message.into();
+        for &channel_id in channel_ids {
+            self.send_message(channel_id, message.clone());
+        }
+    }
+
+    /// Send a message to the server over a channel, and immediately send the packets to the server,
+    /// instead of waiting for the next [send_packets][Self::send_packets] call.
+    pub fn send_message_now<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), RenetError> {
+        self.try_send_message(channel_id, message)?;
+        self.send_packets()
+    }
+
+    /// Returns an iterator over the messages received from the server in all channels, with their channel id.
+    pub fn drain_received(&mut self) -> impl Iterator<Item = (u8, Vec<u8>)> + '_ {
+        self.reliable_connection.drain_received()
+    }
+
+    /// Send a message to the server over a reliable channel and returns its id,
+    /// the id is reported by [delivered_messages][Self::delivered_messages] once the message is acked.
+    pub fn try_send_message_with_id<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<MessageId, RenetError> {
+        if let Some(reason) = self.netcode_client.disconnected() {
+            return Err(NetcodeError::Disconnected(reason).into());
+        }
+        let message_id = self.reliable_connection.try_send_message_with_id(channel_id, message)?;
+        Ok(message_id)
+    }
+
+    /// Returns the reliable messages sent to the server that were acked in the last update.
+    pub fn delivered_messages(&self) -> &[MessageId] {
+        self.reliable_connection.delivered_messages()
+    }
+
+    /// Returns the reliable messages sent to the server that expired in the last update without being acked.
+    pub fn expired_messages(&self) -> &[MessageId] {
+        self.reliable_connection.expired_messages()
+    }
+
+    /// Send an unreliable datagram to the server outside the channels,
+    /// returns an error if the datagram is too large or the client is disconnected.
+    pub fn send_unreliable<B: Into<Bytes>>(&mut self, payload: B) -> Result<(), RenetError> {
+        if let Some(reason) = self.netcode_client.disconnected() {
+            return Err(NetcodeError::Disconnected(reason).into());
+        }
+        self.reliable_connection.send_unreliable(payload)?;
+        Ok(())
+    }
+
+    /// Receive an unreliable datagram sent by the server outside the channels.
+    pub fn receive_unreliable(&mut self) -> Option<Vec<u8>> {
+        self.reliable_connection.receive_unreliable()
+    }
+
     /// Verifies if a message can be sent to the server over a channel.
     pub fn can_send_message<I: Into<u8>>(&self, channel_id: I) -> bool {
         self.reliable_connection.can_send_message(channel_id)
     }
 
+    /// Returns true if there are messages ready to be sent to the server in the next packets.
+    pub fn has_data_to_send(&self) -> bool {
+        self.reliable_connection.has_data_to_send()
+    }
+
+    /// Returns the number of messages in the channel waiting to be sent or acked by the server.
+    pub fn pending_messages<I: Into<u8>>(&self, channel_id: I) -> usize {
+        self.reliable_connection.pending_messages(channel_id)
+    }
+
+    /// Returns the number of bytes of the messages in the channel waiting to be sent or acked by the server.
+    pub fn pending_bytes<I: Into<u8>>(&self, channel_id: I) -> usize {
+        self.reliable_connection.pending_bytes(channel_id)
+    }
+
     pub fn network_info(&self) -> NetworkInfo {
         NetworkInfo {
             sent_kbps: self.client_packet_info.sent_kbps,
             received_kbps: self.client_packet_info.received_kbps,
             rtt: self.reliable_connection.rtt(),
+            rtt_variance: self.reliable_connection.rtt_variance(),
             packet_loss: self.reliable_connection.packet_loss(),
+            packets_in_flight: self.reliable_connection.packets_in_flight(),
         }
     }
 
+    /// Returns the time since packets were last sent to the server, a value much higher than
+    /// the heartbeat time means that [send_packets][Self::send_packets] is not called often enough.
+    pub fn time_since_last_send(&self) -> Duration {
+        self.reliable_connection.time_since_last_send()
+    }
+
+    /// Returns the time left before the connection times out if no packets are received from the server.
+    pub fn time_until_timeout(&self) -> Duration {
+        self.netcode_client.time_until_timeout()
+    }
+
+    /// Returns the time left before a heartbeat is sent to the server, if no other packet is sent.
+    pub fn time_until_heartbeat(&self) -> Duration {
+        self.reliable_connection.time_until_heartbeat()
+    }
+
+    /// Returns the RTT measured by the last ping sent to the server,
+    /// pings are only sent when the ping interval is set in the connection config.
+    pub fn last_ping_rtt(&self) -> Option<Duration> {
+        self.reliable_connection.last_ping_rtt()
+    }
+
+    /// Returns the lifetime totals of the packets sent, received and acked,
+    /// the bytes don't include the netcode header.
+    pub fn totals(&self) -> ConnectionTotals {
+        self.reliable_connection.totals()
+    }
+
+    /// Returns the path MTU discovered with the server,
+    /// only available when the MTU discovery is set in the connection config.
+    pub fn discovered_mtu(&self) -> Option<u64> {
+        self.reliable_connection.discovered_mtu()
+    }
+
+    /// Returns the network conditions detected by the congestion control.
+    pub fn congestion_state(&self) -> CongestionState {
+        self.reliable_connection.congestion_state()
+    }
+
+    /// Returns the network info of a single channel, the sent and received
+    /// kilobits per second only account for the messages from that channel.
+    /// Returns None if the channel does not exist.
+    pub fn channel_network_info<I: Into<u8>>(&self, channel_id: I) -> Option<NetworkInfo> {
+        let bandwidth = self.reliable_connection.channel_bandwidth(channel_id)?;
+
+        Some(NetworkInfo {
+            sent_kbps: bandwidth.sent_kbps,
+            received_kbps: bandwidth.received_kbps,
+            rtt: self.reliable_connection.rtt(),
+            rtt_variance: self.reliable_connection.rtt_variance(),
+            packet_loss: self.reliable_connection.packet_loss(),
+            packets_in_flight: self.reliable_connection.packets_in_flight(),
+        })
+    }
+
     /// Send packets to the server.
+    /// When the transport send buffer is full, the packets are queued and sent in the next call,
+    /// see [blocked_packets][Self::blocked_packets].
     pub fn send_packets(&mut self) -> Result<(), RenetError> {
+        self.blocked_packets.resend(&mut self.transport)?;
+        if self.netcode_client.is_connected() {
+            // The packets are borrowed from the connection send buffer, the first error stops sending
+            let mut result = Ok(());
+            self.reliable_connection.for_each_packet_to_send(|packet| {
+                if result.is_ok() {
+                    result = send_payload_packet(
+                        self.current_time,
+                        &mut self.netcode_client,
+                        &mut self.transport,
+                        &mut self.blocked_packets,
+                        &mut self.client_packet_info,
+                        packet,
+                    );
+                }
+            })?;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of packets waiting to be sent because the transport send buffer was full.
+    pub fn blocked_packets(&self) -> usize {
+        self.blocked_packets.len()
+    }
+
+    /// Send packets to the server until all the queued data is sent, respecting the bandwidth limit.
+    /// Unlike [send_packets][Self::send_packets], that sends at most one packet with data per call.
+    pub fn flush(&mut self) -> Result<(), RenetError> {
+        self.blocked_packets.resend(&mut self.transport)?;
         if self.netcode_client.is_connected() {
-            let packets = self.reliable_connection.get_packets_to_send()?;
-            for packet in packets.into_iter() {
-                let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
-                send_to(self.current_time, &self.socket, &mut self.client_packet_info, payload, addr)?;
+            let packets = self.reliable_connection.flush_packets()?;
+            for packet in packets.iter() {
+                send_payload_packet(
+                    self.current_time,
+                    &mut self.netcode_client,
+                    &mut self.transport,
+                    &mut self.blocked_packets,
+                    &mut self.client_packet_info,
+                    packet,
+                )?;
             }
         }
         Ok(())
     }
 
+    /// Processes a packet received from the address, for transports that don't receive
+    /// the packets themselves. Packets received by the transport are processed in [update][Self::update].
+    pub fn process_packet(&mut self, addr: SocketAddr, packet: &mut [u8]) -> Result<(), RenetError> {
+        if addr != self.netcode_client.server_addr() {
+            debug!("Discarded packet from unknown server {:?}", addr);
+            self.discarded_packets += 1;
+            return Ok(());
+        }
+
+        let packet_info = PacketInfo::new(self.current_time, packet.len());
+        self.client_packet_info.add_packet_received(packet_info);
+
+        if let Some(payload) = self.netcode_client.process_packet(packet) {
+            self.reliable_connection.process_packet(payload)?;
+        }
+
+        Ok(())
+    }
+
     /// Advances the client by duration, and receive packets from the network.
     pub fn update(&mut self, duration: Duration) -> Result<(), RenetError> {
         self.current_time += duration;
@@ -189,30 +511,28 @@ impl RenetClient {
         }
 
         loop {
-            let packet = match self.socket.recv_from(&mut self.buffer) {
+            match self.transport.recv(&mut self.buffer) {
                 Ok((len, addr)) => {
-                    if addr != self.netcode_client.server_addr() {
-                        debug!("Discarded packet from unknown server {:?}", addr);
-                        continue;
-                    }
-
-                    &mut self.buffer[..len]
+                    let mut buffer = std::mem::take(&mut self.buffer);
+                    let result = self.process_packet(addr, &mut buffer[..len]);
+                    self.buffer = buffer;
+                    result?;
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
                 Err(e) => return Err(RenetError::IO(e)),
             };
-
-            let packet_info = PacketInfo::new(self.current_time, packet.len());
-            self.client_packet_info.add_packet_received(packet_info);
-
-            if let Some(payload) = self.netcode_client.process_packet(packet) {
-                self.reliable_connection.process_packet(payload)?;
-            }
         }
 
         self.reliable_connection.update()?;
         if let Some((packet, addr)) = self.netcode_client.update(duration) {
-            send_to(self.current_time, &self.socket, &mut self.client_packet_info, packet, addr)?;
+            send_to(
+                self.current_time,
+                &mut self.transport,
+                &mut self.blocked_packets,
+                &mut self.client_packet_info,
+                packet,
+                addr,
+            )?;
         }
 
         self.client_packet_info.update_metrics();
@@ -221,14 +541,52 @@ impl RenetClient {
     }
 }
 
-fn send_to(
+fn send_payload_packet<T: Transport>(
+    current_time: Duration,
+    netcode_client: &mut NetcodeClient,
+    transport: &mut T,
+    blocked_packets: &mut BlockedPackets,
+    client_packet_info: &mut ClientPacketInfo,
+    packet: &[u8],
+) -> Result<(), RenetError> {
+    let (addr, payload) = netcode_client.generate_payload_packet(packet)?;
+    send_to(current_time, transport, blocked_packets, client_packet_info, payload, addr)?;
+    Ok(())
+}
+
+fn send_to<T: Transport>(
     current_time: Duration,
-    socket: &UdpSocket,
+    transport: &mut T,
+    blocked_packets: &mut BlockedPackets,
     client_packet_info: &mut ClientPacketInfo,
     packet: &[u8],
     address: SocketAddr,
-) -> Result<usize, std::io::Error> {
+) -> Result<(), std::io::Error> {
     let packet_info = PacketInfo::new(current_time, packet.len());
     client_packet_info.add_packet_sent(packet_info);
-    socket.send_to(packet, address)
+    blocked_packets.send_to(transport, packet, address)
+}
+
+#[cfg(all(test, feature = "udp"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_family_mismatch() {
+        let socket = UdpSocket::bind("[::1]:0").unwrap();
+        let local_addr = socket.local_addr().unwrap();
+        let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let authentication = ClientAuthentication::Unsecure {
+            client_id: 0,
+            server_addr,
+            user_data: None,
+            protocol_id: 0,
+        };
+
+        let error = RenetClient::new(Duration::ZERO, socket, RenetConnectionConfig::default(), authentication).err();
+        assert!(matches!(
+            error,
+            Some(RenetError::AddressFamilyMismatch { local_addr: l, server_addr: s }) if l == local_addr && s == server_addr
+        ));
+    }
 }
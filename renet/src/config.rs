@@ -1,4 +1,8 @@
-use rechannel::{channel::ChannelConfig, remote_connection::ConnectionConfig, FragmentConfig};
+use rechannel::{
+    channel::ChannelConfig,
+    remote_connection::{CongestionConfig, ConnectionConfig, MtuDiscoveryConfig},
+    FragmentConfig,
+};
 use renetcode::NETCODE_MAX_PAYLOAD_BYTES;
 
 use std::time::Duration;
@@ -24,6 +28,31 @@ pub struct RenetConnectionConfig {
     pub bandwidth_smoothing_factor: f32,
     /// Value which specifies at which interval a heartbeat should be sent, if no other packet was sent in the meantime.
     pub heartbeat_time: Duration,
+    /// Number of previous packets acked in each sent packet, max 64.
+    pub ack_window_size: u8,
+    /// When set, a heartbeat is sent immediately once this number of received packets are waiting to be acked.
+    pub max_pending_acks: Option<usize>,
+    /// When set, limits the bandwidth used to send packets, messages above the limit are delayed.
+    pub max_send_bandwidth_kbps: Option<f32>,
+    /// When set, the ack bits are sent inverted to save a few bytes per packet,
+    /// the client and server must use the same value.
+    pub compact_acks: bool,
+    /// Sequence of the first packet sent, useful to test the sequence wraparound.
+    pub initial_sequence: u16,
+    /// When set, reliable messages are resent as soon as a packet sent this many sequences later is acked
+    /// without the packet that carried them, instead of waiting for the resend time.
+    pub nack_threshold: Option<u8>,
+    /// When set, the send bandwidth is adjusted based on the packet loss and RTT.
+    pub congestion_control: Option<CongestionConfig>,
+    /// When set, a ping is sent at this interval to measure the RTT even when no messages are sent.
+    pub ping_interval: Option<Duration>,
+    /// When set, the path MTU is discovered at the start of the connection, and packets above it are fragmented.
+    /// The probes are limited by the max payload of the netcode packets, and the fragment size is lowered to fit
+    /// in the min size of the discovery, so the client and server should both set it with the same min size.
+    pub mtu_discovery: Option<MtuDiscoveryConfig>,
+    /// When set, the pending messages are coalesced into packets up to this size (bytes) instead of a single
+    /// fragmented packet, like 1200 bytes to stay under the typical path MTU.
+    pub coalesce_mtu: Option<u64>,
     /// Channels configuration that this client/server will use to send messages.
     pub send_channels_config: Vec<ChannelConfig>,
     /// Channels configuration that this client/server will use to receive messages.
@@ -47,6 +76,16 @@ impl Default for RenetConnectionConfig {
             packet_loss_smoothing_factor: 0.1,
             bandwidth_smoothing_factor: 0.1,
             heartbeat_time: Duration::from_millis(100),
+            ack_window_size: 32,
+            max_pending_acks: None,
+            max_send_bandwidth_kbps: None,
+            compact_acks: false,
+            initial_sequence: 0,
+            nack_threshold: None,
+            congestion_control: None,
+            ping_interval: None,
+            mtu_discovery: None,
+            coalesce_mtu: None,
             send_channels_config: channels_config.clone(),
             receive_channels_config: channels_config,
         }
@@ -55,11 +94,20 @@ impl Default for RenetConnectionConfig {
 
 impl RenetConnectionConfig {
     pub fn to_connection_config(&self) -> ConnectionConfig {
-        let fragment_config = FragmentConfig {
+        let mut fragment_config = FragmentConfig {
             fragment_above: NETCODE_MAX_PAYLOAD_BYTES as u64 - 40,
             fragment_size: NETCODE_MAX_PAYLOAD_BYTES - 40,
             reassembly_buffer_size: self.reassembly_buffer_size,
+            ..Default::default()
         };
+        if let Some(mtu_discovery) = &self.mtu_discovery {
+            let mtu_fragment_size = mtu_discovery.min_size.saturating_sub(40) as usize;
+            fragment_config.fragment_size = fragment_config.fragment_size.min(mtu_fragment_size);
+        }
+        let mtu_discovery = self.mtu_discovery.clone().map(|mut mtu_discovery| {
+            mtu_discovery.max_size = mtu_discovery.max_size.min(NETCODE_MAX_PAYLOAD_BYTES as u64);
+            mtu_discovery
+        });
 
         ConnectionConfig {
             max_packet_size: self.max_packet_size,
@@ -68,6 +116,16 @@ impl RenetConnectionConfig {
             rtt_smoothing_factor: self.rtt_smoothing_factor,
             packet_loss_smoothing_factor: self.packet_loss_smoothing_factor,
             heartbeat_time: self.heartbeat_time,
+            ack_window_size: self.ack_window_size,
+            max_pending_acks: self.max_pending_acks,
+            max_send_bandwidth_kbps: self.max_send_bandwidth_kbps,
+            compact_acks: self.compact_acks,
+            initial_sequence: self.initial_sequence,
+            nack_threshold: self.nack_threshold,
+            congestion_control: self.congestion_control.clone(),
+            ping_interval: self.ping_interval,
+            mtu_discovery,
+            coalesce_mtu: self.coalesce_mtu,
             send_channels_config: self.send_channels_config.clone(),
             receive_channels_config: self.receive_channels_config.clone(),
             fragment_config,
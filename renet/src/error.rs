@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt;
+use std::net::SocketAddr;
 
 use rechannel::error::DisconnectionReason as RechannelDisconnectReason;
 use renetcode::DisconnectReason as NetcodeDisconnectReason;
@@ -10,9 +11,27 @@ pub enum RenetError {
     Netcode(renetcode::NetcodeError),
     Rechannel(rechannel::error::RechannelError),
     IO(std::io::Error),
+    /// The socket and the server address are from different IP versions.
+    AddressFamilyMismatch {
+        local_addr: SocketAddr,
+        server_addr: SocketAddr,
+    },
+    /// Custom error from the application or from custom implementations,
+    /// like a [PacketSerializer](rechannel::serialization::PacketSerializer).
+    Custom(Box<dyn Error + Send + Sync + 'static>),
 }
 
-impl Error for RenetError {}
+impl Error for RenetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            RenetError::Netcode(ref err) => Some(err),
+            RenetError::Rechannel(ref err) => Some(err),
+            RenetError::IO(ref err) => Some(err),
+            RenetError::AddressFamilyMismatch { .. } => None,
+            RenetError::Custom(ref err) => Some(err.as_ref()),
+        }
+    }
+}
 
 impl fmt::Display for RenetError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -20,6 +39,12 @@ impl fmt::Display for RenetError {
             RenetError::Netcode(ref err) => err.fmt(fmt),
             RenetError::Rechannel(ref err) => err.fmt(fmt),
             RenetError::IO(ref err) => err.fmt(fmt),
+            RenetError::AddressFamilyMismatch { local_addr, server_addr } => write!(
+                fmt,
+                "socket address {} and server address {} are from different IP versions",
+                local_addr, server_addr
+            ),
+            RenetError::Custom(ref err) => err.fmt(fmt),
         }
     }
 }
@@ -42,12 +67,20 @@ impl From<rechannel::error::RechannelError> for RenetError {
     }
 }
 
+impl RenetError {
+    /// Creates a custom error from any error type.
+    pub fn custom<E: Into<Box<dyn Error + Send + Sync + 'static>>>(error: E) -> Self {
+        RenetError::Custom(error.into())
+    }
+}
+
 impl From<std::io::Error> for RenetError {
     fn from(inner: std::io::Error) -> Self {
         RenetError::IO(inner)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DisconnectionReason {
     Rechannel(RechannelDisconnectReason),
     Netcode(NetcodeDisconnectReason),
@@ -1,21 +1,25 @@
+mod blocked_packets;
 mod circular_buffer;
 mod client;
 mod config;
 mod error;
 mod network_info;
 mod server;
+mod transport;
 
 pub use rechannel::channel::{ChannelConfig, ChunkChannelConfig, DefaultChannel, ReliableChannelConfig, UnreliableChannelConfig};
 pub use rechannel::error::{ChannelError, DisconnectionReason, RechannelError};
+pub use rechannel::remote_connection::{CongestionConfig, CongestionState, ConnectionTotals, MessageId, MtuDiscoveryConfig};
 
 pub use renetcode::{generate_random_bytes, ConnectToken, NetcodeError};
 pub use renetcode::{NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
 
-pub use client::{ClientAuthentication, RenetClient};
+pub use client::{ClientAuthentication, ConnectionState, RenetClient};
 pub use config::RenetConnectionConfig;
 pub use error::RenetError;
 pub use network_info::NetworkInfo;
 pub use server::{RenetServer, ServerAuthentication, ServerConfig, ServerEvent};
+pub use transport::{FilteredTransport, PacketFilter, Transport};
 
 // Reused in the renet_visualizer crate
 #[doc(hidden)]
@@ -9,11 +9,17 @@ const CIRCULAR_BUFFER_SIZE: usize = 60;
 pub struct NetworkInfo {
     /// Round-trip Time
     pub rtt: f32,
+    /// Round-trip Time variation (jitter).
+    pub rtt_variance: f32,
     /// Sent kilobits per second.
+    /// Counts the whole packets sent to the socket, including the packet headers,
+    /// the encryption overhead and each fragment of a fragmented packet.
     pub sent_kbps: f32,
-    /// Received kilobits per second.
+    /// Received kilobits per second, counted the same way as the sent kilobits.
     pub received_kbps: f32,
     pub packet_loss: f32,
+    /// Sent packets that were not acked yet.
+    pub packets_in_flight: usize,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
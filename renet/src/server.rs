@@ -1,5 +1,8 @@
 use crate::{
+    blocked_packets::BlockedPackets,
+    error::{DisconnectionReason as RenetDisconnectionReason, RenetError},
     network_info::{ClientPacketInfo, NetworkInfo, PacketInfo},
+    transport::Transport,
     RenetConnectionConfig,
 };
 
@@ -11,19 +14,27 @@ use std::{
 };
 
 use log::error;
-use rechannel::{disconnect_packet, error::DisconnectionReason, server::RechannelServer, Bytes};
+use rechannel::{
+    disconnect_packet,
+    error::{DisconnectionReason, RechannelError},
+    remote_connection::{CongestionState, ConnectionTotals, MessageId},
+    server::RechannelServer,
+    Bytes,
+};
 use renetcode::{NetcodeServer, ServerResult, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
 
 /// A server that can establish authenticated connections with multiple clients.
 /// Can send/receive encrypted messages from/to them.
+/// The packets are sent with an [UdpSocket] by default, or any other [Transport].
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
-pub struct RenetServer {
-    socket: UdpSocket,
+pub struct RenetServer<T = UdpSocket> {
+    transport: T,
     reliable_server: RechannelServer<u64>,
     netcode_server: NetcodeServer,
     bandwidth_smoothing_factor: f32,
     clients_packet_info: HashMap<SocketAddr, ClientPacketInfo>,
+    blocked_packets: BlockedPackets,
     buffer: Box<[u8]>,
     events: VecDeque<ServerEvent>,
 }
@@ -32,7 +43,7 @@ pub struct RenetServer {
 #[derive(Debug, Clone)]
 pub enum ServerEvent {
     ClientConnected(u64, Box<[u8; NETCODE_USER_DATA_BYTES]>),
-    ClientDisconnected(u64),
+    ClientDisconnected(u64, RenetDisconnectionReason),
 }
 
 /// Configuration to establish a secure or unsecure connection with the server.
@@ -75,6 +86,7 @@ impl ServerConfig {
     }
 }
 
+#[cfg(feature = "udp")]
 impl RenetServer {
     pub fn new(
         current_time: Duration,
@@ -82,6 +94,27 @@ impl RenetServer {
         connection_config: RenetConnectionConfig,
         socket: UdpSocket,
     ) -> Result<Self, std::io::Error> {
+        socket.set_nonblocking(true)?;
+        Ok(Self::with_transport(current_time, server_config, connection_config, socket))
+    }
+
+    #[doc(hidden)]
+    pub fn __test() -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_config = ServerConfig::new(64, 0, socket.local_addr().unwrap(), ServerAuthentication::Unsecure);
+        Self::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap()
+    }
+}
+
+impl<T: Transport> RenetServer<T> {
+    /// Creates a server that sends and receives the packets with the transport,
+    /// the transport should not block when there are no packets to receive.
+    pub fn with_transport(
+        current_time: Duration,
+        server_config: ServerConfig,
+        connection_config: RenetConnectionConfig,
+        transport: T,
+    ) -> Self {
         let buffer = vec![0u8; connection_config.max_packet_size as usize].into_boxed_slice();
         let bandwidth_smoothing_factor = connection_config.bandwidth_smoothing_factor;
         let reliable_server = RechannelServer::new(current_time, connection_config.to_connection_config());
@@ -100,24 +133,24 @@ impl RenetServer {
             private_key,
         );
 
-        socket.set_nonblocking(true)?;
-
-        Ok(Self {
-            socket,
+        Self {
+            transport,
             netcode_server,
             reliable_server,
             bandwidth_smoothing_factor,
             buffer,
             clients_packet_info: HashMap::new(),
+            blocked_packets: BlockedPackets::default(),
             events: VecDeque::new(),
-        })
+        }
     }
 
-    #[doc(hidden)]
-    pub fn __test() -> Self {
-        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
-        let server_config = ServerConfig::new(64, 0, socket.local_addr().unwrap(), ServerAuthentication::Unsecure);
-        Self::new(Duration::ZERO, server_config, RenetConnectionConfig::default(), socket).unwrap()
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
     }
 
     pub fn addr(&self) -> SocketAddr {
@@ -136,7 +169,8 @@ impl RenetServer {
             server_result,
             current_time,
             self.bandwidth_smoothing_factor,
-            &self.socket,
+            &mut self.transport,
+            &mut self.blocked_packets,
             &mut self.reliable_server,
             &mut self.clients_packet_info,
             &mut self.events,
@@ -145,6 +179,28 @@ impl RenetServer {
         }
     }
 
+    /// Disconnects a client, the reason is sent to the client in the next update.
+    pub fn disconnect_with_reason(&mut self, client_id: u64, reason: DisconnectionReason) {
+        self.reliable_server.disconnect_with_reason(&client_id, reason);
+    }
+
+    /// Pauses the server, for example when the application is suspended. The time passed while
+    /// paused is not counted towards the clients timeout, and no heartbeats or resends are sent.
+    pub fn pause(&mut self) {
+        self.netcode_server.pause();
+        self.reliable_server.pause();
+    }
+
+    /// Resumes the server from where it was paused.
+    pub fn resume(&mut self) {
+        self.netcode_server.resume();
+        self.reliable_server.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.netcode_server.is_paused()
+    }
+
     /// Disconnects all connected clients.
     pub fn disconnect_clients(&mut self) {
         for client_id in self.netcode_server.clients_id() {
@@ -152,6 +208,22 @@ impl RenetServer {
         }
     }
 
+    /// Returns the network info of a client's channel, the sent and received
+    /// kilobits per second only account for the messages from that channel.
+    /// Returns None if the client or the channel does not exist.
+    pub fn channel_network_info<I: Into<u8>>(&self, client_id: u64, channel_id: I) -> Option<NetworkInfo> {
+        let bandwidth = self.reliable_server.client_channel_bandwidth(client_id, channel_id)?;
+
+        Some(NetworkInfo {
+            sent_kbps: bandwidth.sent_kbps,
+            received_kbps: bandwidth.received_kbps,
+            rtt: self.reliable_server.client_rtt(client_id),
+            rtt_variance: self.reliable_server.client_rtt_variance(client_id),
+            packet_loss: self.reliable_server.client_packet_loss(client_id),
+            packets_in_flight: self.reliable_server.client_packets_in_flight(client_id),
+        })
+    }
+
     /// Returns the client's network info if the client exits.
     pub fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
         let addr = match self.netcode_server.client_addr(client_id) {
@@ -159,21 +231,53 @@ impl RenetServer {
             None => return None,
         };
 
-        let client_packet_info = self.clients_packet_info.get(&addr).unwrap();
+        let client_packet_info = self.clients_packet_info.get(&addr)?;
 
         let sent_kbps = client_packet_info.sent_kbps;
         let received_kbps = client_packet_info.received_kbps;
         let rtt = self.reliable_server.client_rtt(client_id);
+        let rtt_variance = self.reliable_server.client_rtt_variance(client_id);
         let packet_loss = self.reliable_server.client_packet_loss(client_id);
+        let packets_in_flight = self.reliable_server.client_packets_in_flight(client_id);
 
         Some(NetworkInfo {
             received_kbps,
             sent_kbps,
             rtt,
+            rtt_variance,
             packet_loss,
+            packets_in_flight,
         })
     }
 
+    /// Returns the time left before the client times out if no packets are received from it.
+    pub fn time_until_timeout(&self, client_id: u64) -> Option<Duration> {
+        self.netcode_server.time_until_timeout(client_id)
+    }
+
+    /// Returns the RTT measured by the last ping sent to the client,
+    /// pings are only sent when the ping interval is set in the connection config.
+    pub fn last_ping_rtt(&self, client_id: u64) -> Option<Duration> {
+        self.reliable_server.client_last_ping_rtt(client_id)
+    }
+
+    /// Returns the lifetime totals of the packets sent, received and acked with the client,
+    /// the bytes don't include the netcode header.
+    pub fn totals(&self, client_id: u64) -> Option<ConnectionTotals> {
+        self.reliable_server.client_totals(client_id)
+    }
+
+    /// Returns the path MTU discovered with the client,
+    /// only available when the MTU discovery is set in the connection config.
+    pub fn discovered_mtu(&self, client_id: u64) -> Option<u64> {
+        self.reliable_server.client_discovered_mtu(client_id)
+    }
+
+    /// Returns the network conditions of the client detected by the congestion control.
+    pub fn congestion_state(&self, client_id: u64) -> CongestionState {
+        self.reliable_server.client_congestion_state(client_id)
+    }
+
     /// Advances the server by duration, and receive packets from the network.
     pub fn update(&mut self, duration: Duration) -> Result<(), io::Error> {
         self.reliable_server.update_connections(duration);
@@ -182,23 +286,12 @@ impl RenetServer {
         let current_time = self.netcode_server.current_time();
 
         loop {
-            match self.socket.recv_from(&mut self.buffer) {
+            match self.transport.recv(&mut self.buffer) {
                 Ok((len, addr)) => {
-                    if let Some(info) = self.clients_packet_info.get_mut(&addr) {
-                        let packet_info = PacketInfo::new(current_time, len);
-                        info.add_packet_received(packet_info);
-                    }
-
-                    let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
-                    handle_server_result(
-                        server_result,
-                        current_time,
-                        self.bandwidth_smoothing_factor,
-                        &self.socket,
-                        &mut self.reliable_server,
-                        &mut self.clients_packet_info,
-                        &mut self.events,
-                    )?;
+                    let mut buffer = std::mem::take(&mut self.buffer);
+                    let result = self.process_packet(addr, &mut buffer[..len]);
+                    self.buffer = buffer;
+                    result?;
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
                 Err(e) => return Err(e),
@@ -211,7 +304,8 @@ impl RenetServer {
                 server_result,
                 current_time,
                 self.bandwidth_smoothing_factor,
-                &self.socket,
+                &mut self.transport,
+                &mut self.blocked_packets,
                 &mut self.reliable_server,
                 &mut self.clients_packet_info,
                 &mut self.events,
@@ -220,14 +314,14 @@ impl RenetServer {
 
         // Handle disconnected clients from Rechannel
         while let Some((client_id, reason)) = self.reliable_server.disconnected_client() {
-            self.events.push_back(ServerEvent::ClientDisconnected(client_id));
+            self.events.push_back(ServerEvent::ClientDisconnected(client_id, reason.into()));
             if reason != DisconnectionReason::DisconnectedByClient {
                 match disconnect_packet(reason) {
                     Err(e) => error!("Failed to serialize disconnect packet: {}", e),
                     Ok(packet) => match self.netcode_server.generate_payload_packet(client_id, &packet) {
                         Err(e) => error!("Failed to encrypt disconnect packet: {}", e),
                         Ok((addr, payload)) => {
-                            self.socket.send_to(payload, addr)?;
+                            self.blocked_packets.send_to(&mut self.transport, payload, addr)?;
                         }
                     },
                 }
@@ -242,6 +336,37 @@ impl RenetServer {
         Ok(())
     }
 
+    /// Processes a packet received from the address, for transports that don't receive
+    /// the packets themselves. Packets received by the transport are processed in [update][Self::update].
+    pub fn process_packet(&mut self, addr: SocketAddr, packet: &mut [u8]) -> Result<(), io::Error> {
+        let current_time = self.netcode_server.current_time();
+        if let Some(info) = self.clients_packet_info.get_mut(&addr) {
+            let packet_info = PacketInfo::new(current_time, packet.len());
+            info.add_packet_received(packet_info);
+        }
+
+        let server_result = self.netcode_server.process_packet(addr, packet);
+        handle_server_result(
+            server_result,
+            current_time,
+            self.bandwidth_smoothing_factor,
+            &mut self.transport,
+            &mut self.blocked_packets,
+            &mut self.reliable_server,
+            &mut self.clients_packet_info,
+            &mut self.events,
+        )?;
+
+        // Move the packet info from the previous address of a client that changed its address
+        if let Some((_, previous_addr)) = self.netcode_server.rebinded_client() {
+            if let Some(info) = self.clients_packet_info.remove(&previous_addr) {
+                self.clients_packet_info.insert(addr, info);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Receive a message from a client over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, client_id: u64, channel_id: I) -> Option<Vec<u8>> {
         self.reliable_server.receive_message(&client_id, channel_id)
@@ -252,11 +377,93 @@ impl RenetServer {
         self.reliable_server.can_send_message(&client_id, channel_id)
     }
 
+    /// Returns true if there are messages ready to be sent to the client in the next packets.
+    pub fn has_data_to_send(&self, client_id: u64) -> bool {
+        self.reliable_server.has_data_to_send(&client_id)
+    }
+
+    /// Returns the number of messages in the channel waiting to be sent or acked by the client.
+    pub fn pending_messages<I: Into<u8>>(&self, client_id: u64, channel_id: I) -> usize {
+        self.reliable_server.pending_messages(&client_id, channel_id)
+    }
+
+    /// Returns the number of bytes of the messages in the channel waiting to be sent or acked by the client.
+    pub fn pending_bytes<I: Into<u8>>(&self, client_id: u64, channel_id: I) -> usize {
+        self.reliable_server.pending_bytes(&client_id, channel_id)
+    }
+
     /// Send a message to a client over a channel.
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) {
         self.reliable_server.send_message(&client_id, channel_id, message);
     }
 
+    /// Send the same message to a client over multiple channels, without copying the message.
+    pub fn send_message_multi<B: Into<Bytes>>(&mut self, client_id: u64, channel_ids: &[u8], message: B) {
+        let message = message.into();
+        for &channel_id in channel_ids {
+            self.send_message(client_id, channel_id, message.clone());
+        }
+    }
+
+    /// Send a message to a client over a channel, returns an error if the client or the channel does not exist.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) -> Result<(), RenetError> {
+        self.reliable_server.try_send_message(&client_id, channel_id, message)?;
+        Ok(())
+    }
+
+    /// Send a message to a client over a channel with a priority, higher priority messages
+    /// are sent first when they do not fit in the same packet. Only reliable channels use the priority.
+    pub fn try_send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: u64,
+        channel_id: I,
+        message: B,
+        priority: u8,
+    ) -> Result<(), RenetError> {
+        self.reliable_server
+            .try_send_message_with_priority(&client_id, channel_id, message, priority)?;
+        Ok(())
+    }
+
+    /// Returns an iterator over the messages received from a client in all channels, with their channel id.
+    pub fn drain_received(&mut self, client_id: u64) -> impl Iterator<Item = (u8, Vec<u8>)> + '_ {
+        self.reliable_server.drain_received(&client_id)
+    }
+
+    /// Send a message to a client over a reliable channel and returns its id,
+    /// the id is reported by [delivered_messages][Self::delivered_messages] once the message is acked.
+    pub fn try_send_message_with_id<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: u64,
+        channel_id: I,
+        message: B,
+    ) -> Result<MessageId, RenetError> {
+        let message_id = self.reliable_server.try_send_message_with_id(&client_id, channel_id, message)?;
+        Ok(message_id)
+    }
+
+    /// Returns the reliable messages sent to a client that were acked in the last update.
+    pub fn delivered_messages(&self, client_id: u64) -> &[MessageId] {
+        self.reliable_server.delivered_messages(&client_id)
+    }
+
+    /// Returns the reliable messages sent to a client that expired in the last update without being acked.
+    pub fn expired_messages(&self, client_id: u64) -> &[MessageId] {
+        self.reliable_server.expired_messages(&client_id)
+    }
+
+    /// Send an unreliable datagram to a client outside the channels,
+    /// returns an error if the client does not exist or the datagram is too large.
+    pub fn send_unreliable<B: Into<Bytes>>(&mut self, client_id: u64, payload: B) -> Result<(), RenetError> {
+        self.reliable_server.send_unreliable(&client_id, payload)?;
+        Ok(())
+    }
+
+    /// Receive an unreliable datagram sent by a client outside the channels.
+    pub fn receive_unreliable(&mut self, client_id: u64) -> Option<Vec<u8>> {
+        self.reliable_server.receive_unreliable(&client_id)
+    }
+
     /// Send a message to all client, except the specified one, over a channel.
     pub fn broadcast_message_except<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) {
         self.reliable_server.broadcast_message_except(&client_id, channel_id, message)
@@ -268,25 +475,88 @@ impl RenetServer {
     }
 
     /// Send packets to connected clients.
-    pub fn send_packets(&mut self) -> Result<(), io::Error> {
+    /// When the transport send buffer is full, the packets are queued and sent in the next call,
+    /// see [blocked_packets][Self::blocked_packets].
+    /// Errors from the transport are returned, errors from a single client connection are logged
+    /// so they don't prevent sending the packets of the other clients.
+    pub fn send_packets(&mut self) -> Result<(), RenetError> {
+        self.blocked_packets.resend(&mut self.transport)?;
         for client_id in self.reliable_server.connections_id().into_iter() {
-            let packets = match self.reliable_server.get_packets_to_send(&client_id) {
-                Ok(p) => p,
-                Err(e) => {
-                    error!("Failed to get packets from {}: {}", client_id, e);
-                    continue;
-                }
-            };
+            self.send_client_packets(client_id)?;
+        }
 
-            let current_time = self.netcode_server.current_time();
-            for packet in packets.iter() {
-                match self.netcode_server.generate_payload_packet(client_id, packet) {
-                    Ok((addr, payload)) => {
-                        send_to(current_time, &self.socket, &mut self.clients_packet_info, payload, addr)?;
-                    }
-                    Err(e) => error!("Failed to encrypt payload packet: {}", e),
-                }
+        Ok(())
+    }
+
+    /// Returns the number of packets waiting to be sent because the transport send buffer was full.
+    pub fn blocked_packets(&self) -> usize {
+        self.blocked_packets.len()
+    }
+
+    /// Send packets to connected clients until all the queued data is sent, respecting the bandwidth limit.
+    /// Unlike [send_packets][Self::send_packets], that sends at most one packet with data per client.
+    pub fn flush(&mut self) -> Result<(), RenetError> {
+        self.blocked_packets.resend(&mut self.transport)?;
+        for client_id in self.reliable_server.connections_id().into_iter() {
+            let packets = self.reliable_server.flush_packets(&client_id);
+            self.send_payload_packets(client_id, packets)?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a message to a client over a channel, and immediately send the packets to the client,
+    /// instead of waiting for the next [send_packets][Self::send_packets] call.
+    pub fn send_message_now<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: u64, channel_id: I, message: B) -> Result<(), RenetError> {
+        self.reliable_server.try_send_message(&client_id, channel_id, message)?;
+        self.send_client_packets(client_id)?;
+        Ok(())
+    }
+
+    fn send_client_packets(&mut self, client_id: u64) -> Result<(), RenetError> {
+        // The packets are borrowed from the connection send buffer, the first error stops sending
+        let current_time = self.netcode_server.current_time();
+        let mut result = Ok(());
+        let packets = self.reliable_server.for_each_packet_to_send(&client_id, |packet| {
+            if result.is_ok() {
+                result = send_payload_packet(
+                    current_time,
+                    &mut self.netcode_server,
+                    &mut self.transport,
+                    &mut self.blocked_packets,
+                    &mut self.clients_packet_info,
+                    client_id,
+                    packet,
+                );
             }
+        });
+        if let Err(e) = packets {
+            error!("Failed to get packets from {}: {}", client_id, e);
+        }
+
+        Ok(result?)
+    }
+
+    fn send_payload_packets(&mut self, client_id: u64, packets: Result<Vec<Vec<u8>>, RechannelError>) -> Result<(), RenetError> {
+        let packets = match packets {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed to get packets from {}: {}", client_id, e);
+                return Ok(());
+            }
+        };
+
+        let current_time = self.netcode_server.current_time();
+        for packet in packets.iter() {
+            send_payload_packet(
+                current_time,
+                &mut self.netcode_server,
+                &mut self.transport,
+                &mut self.blocked_packets,
+                &mut self.clients_packet_info,
+                client_id,
+                packet,
+            )?;
         }
 
         Ok(())
@@ -322,11 +592,13 @@ impl RenetServer {
     }
 }
 
-fn handle_server_result(
+#[allow(clippy::too_many_arguments)]
+fn handle_server_result<T: Transport>(
     server_result: ServerResult,
     current_time: Duration,
     bandwidth_smoothing_factor: f32,
-    socket: &UdpSocket,
+    transport: &mut T,
+    blocked_packets: &mut BlockedPackets,
     reliable_server: &mut RechannelServer<u64>,
     packet_infos: &mut HashMap<SocketAddr, ClientPacketInfo>,
     events: &mut VecDeque<ServerEvent>,
@@ -334,7 +606,7 @@ fn handle_server_result(
     match server_result {
         ServerResult::None => {}
         ServerResult::PacketToSend { payload, addr } => {
-            send_to(current_time, socket, packet_infos, payload, addr)?;
+            send_to(current_time, transport, blocked_packets, packet_infos, payload, addr)?;
         }
         ServerResult::Payload { client_id, payload } => {
             if !reliable_server.is_connected(&client_id) {
@@ -354,14 +626,19 @@ fn handle_server_result(
             reliable_server.add_connection(&client_id);
             packet_infos.insert(addr, ClientPacketInfo::new(bandwidth_smoothing_factor));
             events.push_back(ServerEvent::ClientConnected(client_id, user_data));
-            send_to(current_time, socket, packet_infos, payload, addr)?;
+            send_to(current_time, transport, blocked_packets, packet_infos, payload, addr)?;
         }
-        ServerResult::ClientDisconnected { client_id, addr, payload } => {
-            events.push_back(ServerEvent::ClientDisconnected(client_id));
+        ServerResult::ClientDisconnected {
+            client_id,
+            addr,
+            reason,
+            payload,
+        } => {
+            events.push_back(ServerEvent::ClientDisconnected(client_id, reason.into()));
             reliable_server.remove_connection(&client_id);
             packet_infos.remove(&addr);
             if let Some(payload) = payload {
-                socket.send_to(payload, addr)?;
+                blocked_packets.send_to(transport, payload, addr)?;
             }
         }
     }
@@ -369,16 +646,35 @@ fn handle_server_result(
     Ok(())
 }
 
-fn send_to(
+fn send_payload_packet<T: Transport>(
+    current_time: Duration,
+    netcode_server: &mut NetcodeServer,
+    transport: &mut T,
+    blocked_packets: &mut BlockedPackets,
+    packet_infos: &mut HashMap<SocketAddr, ClientPacketInfo>,
+    client_id: u64,
+    packet: &[u8],
+) -> Result<(), std::io::Error> {
+    match netcode_server.generate_payload_packet(client_id, packet) {
+        Ok((addr, payload)) => send_to(current_time, transport, blocked_packets, packet_infos, payload, addr),
+        Err(e) => {
+            error!("Failed to encrypt payload packet: {}", e);
+            Ok(())
+        }
+    }
+}
+
+fn send_to<T: Transport>(
     current_time: Duration,
-    socket: &UdpSocket,
+    transport: &mut T,
+    blocked_packets: &mut BlockedPackets,
     packet_infos: &mut HashMap<SocketAddr, ClientPacketInfo>,
     packet: &[u8],
     addr: SocketAddr,
-) -> Result<usize, std::io::Error> {
+) -> Result<(), std::io::Error> {
     if let Some(info) = packet_infos.get_mut(&addr) {
         let packet_info = PacketInfo::new(current_time, packet.len());
         info.add_packet_sent(packet_info);
     }
-    socket.send_to(packet, addr)
+    blocked_packets.send_to(transport, packet, addr)
 }
@@ -0,0 +1,318 @@
+use std::io;
+use std::net::SocketAddr;
+#[cfg(feature = "udp")]
+use std::net::UdpSocket;
+
+/// Transport used by the [RenetClient](crate::RenetClient) and [RenetServer](crate::RenetServer)
+/// to send and receive the packets. It's implemented for the [UdpSocket], other transports,
+/// like WebRTC data channels or QUIC streams, can implement it to carry the packets instead.
+/// The [UdpSocket] implementation and constructors are behind the `udp` feature, enabled by default,
+/// disable it on targets without sockets like `wasm32-unknown-unknown`.
+pub trait Transport {
+    /// Sends the packet to the address. When the packet cannot be sent right now,
+    /// returns an [io::ErrorKind::WouldBlock] error, the packet is queued and sent again later.
+    fn send(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<usize>;
+
+    /// Receives a packet in the buffer, returns its length and the address it came from,
+    /// or an [io::ErrorKind::WouldBlock] error when there are no more packets to receive.
+    /// The default implementation never receives packets, for transports that pass
+    /// the received packets with `process_packet` instead.
+    fn recv(&mut self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let _ = buffer;
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+/// Filter applied to the final bytes of the packets, outside of the netcode encryption:
+/// outgoing packets are filtered after they are encrypted and incoming packets before they are decrypted.
+/// Can be used to add a custom header or footer, like an application-level HMAC, or to interop with another wire format.
+pub trait PacketFilter {
+    /// Called with the packet before it is sent to the address, the packet can be modified or extended.
+    fn outgoing(&mut self, packet: &mut Vec<u8>, addr: SocketAddr) {
+        let _ = (packet, addr);
+    }
+
+    /// Called with the packet received from the address, the packet can be modified in place.
+    /// Returns the length of the packet to process, or None to discard it.
+    fn incoming(&mut self, packet: &mut [u8], addr: SocketAddr) -> Option<usize> {
+        let _ = addr;
+        Some(packet.len())
+    }
+}
+
+/// Transport that runs the packets sent and received by another transport through a [PacketFilter].
+/// Packets passed with `process_packet` don't go through the transport, they should be filtered by the caller.
+#[derive(Debug)]
+pub struct FilteredTransport<T, F> {
+    transport: T,
+    filter: F,
+    buffer: Vec<u8>,
+}
+
+impl<T: Transport, F: PacketFilter> FilteredTransport<T, F> {
+    pub fn new(transport: T, filter: F) -> Self {
+        Self {
+            transport,
+            filter,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut F {
+        &mut self.filter
+    }
+}
+
+impl<T: Transport, F: PacketFilter> Transport for FilteredTransport<T, F> {
+    fn send(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(packet);
+        self.filter.outgoing(&mut self.buffer, addr);
+        self.transport.send(&self.buffer, addr)?;
+
+        Ok(packet.len())
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        loop {
+            let (len, addr) = self.transport.recv(buffer)?;
+            match self.filter.incoming(&mut buffer[..len], addr) {
+                Some(len) => return Ok((len, addr)),
+                None => log::trace!("Packet from {} discarded by the filter", addr),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "udp")]
+impl Transport for UdpSocket {
+    fn send(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.send_to(packet, addr)
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv_from(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientAuthentication, RenetClient, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig};
+
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    #[derive(Debug, Default)]
+    struct BufferTransport {
+        packets: Vec<(Vec<u8>, SocketAddr)>,
+        // Simulates a full send buffer
+        blocking: bool,
+    }
+
+    impl Transport for BufferTransport {
+        fn send(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+            if self.blocking {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.packets.push((packet.to_vec(), addr));
+            Ok(packet.len())
+        }
+    }
+
+    const SERVER_ADDR: &str = "127.0.0.1:5000";
+    const CLIENT_ADDR: &str = "127.0.0.1:6000";
+
+    fn new_client_server() -> (RenetClient<BufferTransport>, RenetServer<BufferTransport>) {
+        let server_addr: SocketAddr = SERVER_ADDR.parse().unwrap();
+        let server_config = ServerConfig::new(8, 0, server_addr, ServerAuthentication::Unsecure);
+        let server = RenetServer::with_transport(
+            Duration::ZERO,
+            server_config,
+            RenetConnectionConfig::default(),
+            BufferTransport::default(),
+        );
+        let authentication = ClientAuthentication::Unsecure {
+            client_id: 0,
+            server_addr,
+            user_data: None,
+            protocol_id: 0,
+        };
+        let client = RenetClient::with_transport(
+            Duration::ZERO,
+            BufferTransport::default(),
+            RenetConnectionConfig::default(),
+            authentication,
+        )
+        .unwrap();
+
+        (client, server)
+    }
+
+    fn exchange_packets(client: &mut RenetClient<BufferTransport>, server: &mut RenetServer<BufferTransport>, client_addr: SocketAddr) {
+        let server_addr: SocketAddr = SERVER_ADDR.parse().unwrap();
+        for _ in 0..10 {
+            client.update(Duration::from_millis(100)).unwrap();
+            client.send_packets().unwrap();
+            for (mut packet, addr) in client.transport_mut().packets.drain(..).collect::<Vec<_>>() {
+                assert_eq!(addr, server_addr);
+                server.process_packet(client_addr, &mut packet).unwrap();
+            }
+
+            server.update(Duration::from_millis(100)).unwrap();
+            server.send_packets().unwrap();
+            for (mut packet, addr) in server.transport_mut().packets.drain(..).collect::<Vec<_>>() {
+                assert_eq!(addr, client_addr);
+                client.process_packet(server_addr, &mut packet).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_transport() {
+        let (mut client, mut server) = new_client_server();
+        exchange_packets(&mut client, &mut server, CLIENT_ADDR.parse().unwrap());
+
+        assert!(client.is_connected());
+        assert!(server.is_client_connected(0));
+    }
+
+    #[test]
+    fn pause() {
+        let (mut client, mut server) = new_client_server();
+        exchange_packets(&mut client, &mut server, CLIENT_ADDR.parse().unwrap());
+        assert!(client.is_connected());
+
+        // No packets are exchanged while paused, but the connections don't time out
+        client.pause();
+        server.pause();
+        let timeout = Duration::from_secs(20);
+        client.update(timeout).unwrap();
+        server.update(timeout).unwrap();
+        assert!(client.is_connected());
+        assert!(server.is_client_connected(0));
+
+        client.resume();
+        server.resume();
+        exchange_packets(&mut client, &mut server, CLIENT_ADDR.parse().unwrap());
+        assert!(client.is_connected());
+        assert!(server.is_client_connected(0));
+
+        // Once resumed the connections time out again
+        client.update(timeout).unwrap();
+        server.update(timeout).unwrap();
+        assert!(!client.is_connected());
+        assert!(!server.is_client_connected(0));
+    }
+
+    #[test]
+    fn blocked_disconnect_packet() {
+        let (mut client, mut server) = new_client_server();
+        exchange_packets(&mut client, &mut server, CLIENT_ADDR.parse().unwrap());
+        assert!(client.is_connected());
+
+        // The disconnect packet is queued while the transport would block
+        server.transport_mut().blocking = true;
+        server.disconnect(0);
+        assert_eq!(server.blocked_packets(), 1);
+
+        server.transport_mut().blocking = false;
+        server.flush().unwrap();
+        assert_eq!(server.blocked_packets(), 0);
+
+        let server_addr: SocketAddr = SERVER_ADDR.parse().unwrap();
+        for (mut packet, _) in server.transport_mut().packets.drain(..).collect::<Vec<_>>() {
+            client.process_packet(server_addr, &mut packet).unwrap();
+        }
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn client_address_rebinding() {
+        let (mut client, mut server) = new_client_server();
+        exchange_packets(&mut client, &mut server, CLIENT_ADDR.parse().unwrap());
+        assert!(server.network_info(0).is_some());
+
+        // Only clients that stopped sending from their address can change it,
+        // the packets sent to the old address are lost
+        server.update(Duration::from_secs(2)).unwrap();
+        server.send_packets().unwrap();
+        server.transport_mut().packets.clear();
+
+        // The packet info follows the client to its new address
+        let new_client_addr: SocketAddr = "127.0.0.1:6001".parse().unwrap();
+        exchange_packets(&mut client, &mut server, new_client_addr);
+        assert!(client.is_connected());
+        assert_eq!(server.client_addr(0), Some(new_client_addr));
+        assert!(server.network_info(0).is_some());
+    }
+
+    #[derive(Debug, Default)]
+    struct LoopbackTransport {
+        packets: VecDeque<(Vec<u8>, SocketAddr)>,
+    }
+
+    impl Transport for LoopbackTransport {
+        fn send(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+            self.packets.push_back((packet.to_vec(), addr));
+            Ok(packet.len())
+        }
+
+        fn recv(&mut self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+            match self.packets.pop_front() {
+                Some((packet, addr)) => {
+                    buffer[..packet.len()].copy_from_slice(&packet);
+                    Ok((packet.len(), addr))
+                }
+                None => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    struct TagFilter;
+
+    impl PacketFilter for TagFilter {
+        fn outgoing(&mut self, packet: &mut Vec<u8>, _addr: SocketAddr) {
+            packet.push(0xFF);
+        }
+
+        fn incoming(&mut self, packet: &mut [u8], _addr: SocketAddr) -> Option<usize> {
+            match packet.last() {
+                Some(0xFF) => Some(packet.len() - 1),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn filtered_transport() {
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let mut transport = FilteredTransport::new(LoopbackTransport::default(), TagFilter);
+        transport.send(&[1, 2, 3], addr).unwrap();
+        assert_eq!(transport.transport().packets[0].0, vec![1, 2, 3, 0xFF]);
+
+        // Packets without the tag are discarded
+        transport.transport_mut().packets.push_back((vec![4, 5, 6], addr));
+        transport.send(&[7, 8], addr).unwrap();
+
+        let mut buffer = [0u8; 16];
+        let (len, _) = transport.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[1, 2, 3]);
+        let (len, _) = transport.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[7, 8]);
+        let error = transport.recv(&mut buffer).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    }
+}
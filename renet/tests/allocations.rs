@@ -0,0 +1,111 @@
+//! Runs in its own test binary, the global allocator counts the allocations of every test in it.
+use renet::{ClientAuthentication, RenetClient, RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig, Transport};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// Counts the bytes allocated by each thread, so tests running in parallel don't interfere
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.with(|allocated| allocated.set(allocated.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocated_bytes() -> usize {
+    ALLOCATED_BYTES.with(|allocated| allocated.get())
+}
+
+#[derive(Debug, Default)]
+struct BufferTransport {
+    packets: Vec<(Vec<u8>, SocketAddr)>,
+    // Counts the sent bytes instead of keeping the packets
+    discard: bool,
+    sent_bytes: usize,
+}
+
+impl Transport for BufferTransport {
+    fn send(&mut self, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.sent_bytes += packet.len();
+        if !self.discard {
+            self.packets.push((packet.to_vec(), addr));
+        }
+        Ok(packet.len())
+    }
+}
+
+#[test]
+fn send_packets_without_copying_them() {
+    let server_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:6000".parse().unwrap();
+    let server_config = ServerConfig::new(8, 0, server_addr, ServerAuthentication::Unsecure);
+    let mut server = RenetServer::with_transport(
+        Duration::ZERO,
+        server_config,
+        RenetConnectionConfig::default(),
+        BufferTransport::default(),
+    );
+    let authentication = ClientAuthentication::Unsecure {
+        client_id: 0,
+        server_addr,
+        user_data: None,
+        protocol_id: 0,
+    };
+    let mut client = RenetClient::with_transport(
+        Duration::ZERO,
+        BufferTransport::default(),
+        RenetConnectionConfig::default(),
+        authentication,
+    )
+    .unwrap();
+
+    while !client.is_connected() {
+        client.update(Duration::from_millis(100)).unwrap();
+        client.send_packets().unwrap();
+        for (mut packet, _) in client.transport_mut().packets.drain(..).collect::<Vec<_>>() {
+            server.process_packet(client_addr, &mut packet).unwrap();
+        }
+
+        server.update(Duration::from_millis(100)).unwrap();
+        server.send_packets().unwrap();
+        for (mut packet, _) in server.transport_mut().packets.drain(..).collect::<Vec<_>>() {
+            client.process_packet(server_addr, &mut packet).unwrap();
+        }
+    }
+    client.transport_mut().discard = true;
+    server.transport_mut().discard = true;
+
+    // The message is copied into the channel data when the packet is generated,
+    // then the packet is serialized and encrypted in buffers reused between packets.
+    // Copying each packet before sending it would allocate a second copy.
+    const MESSAGE_SIZE: usize = 1000;
+    client.send_message(1, vec![7u8; MESSAGE_SIZE]);
+    let allocated = allocated_bytes();
+    client.send_packets().unwrap();
+    let allocated = allocated_bytes() - allocated;
+    assert!(client.transport_mut().sent_bytes > MESSAGE_SIZE);
+    assert!(allocated < MESSAGE_SIZE * 2, "client allocated {} bytes", allocated);
+
+    server.send_message(0, 1, vec![7u8; MESSAGE_SIZE]);
+    let allocated = allocated_bytes();
+    server.send_packets().unwrap();
+    let allocated = allocated_bytes() - allocated;
+    assert!(server.transport_mut().sent_bytes > MESSAGE_SIZE);
+    assert!(allocated < MESSAGE_SIZE * 2, "server allocated {} bytes", allocated);
+}
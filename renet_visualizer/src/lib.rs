@@ -221,7 +221,7 @@ impl<const N: usize> RenetServerVisualizer<N> {
     /// # let mut visualizer = RenetServerVisualizer::<5>::new(Default::default());
     /// while let Some(event) = renet_server.get_event() {
     ///     match event {
-    ///         ServerEvent::ClientDisconnected(client_id) => {
+    ///         ServerEvent::ClientDisconnected(client_id, _) => {
     ///             visualizer.remove_client(client_id);
     ///             // ...
     ///         }
@@ -105,8 +105,13 @@ fn handle_server_result(
             usernames.insert(client_id, username.0);
             socket.send_to(payload, addr).unwrap();
         }
-        ServerResult::ClientDisconnected { client_id, addr, payload } => {
-            println!("Client {} disconnected.", client_id);
+        ServerResult::ClientDisconnected {
+            client_id,
+            addr,
+            reason,
+            payload,
+        } => {
+            println!("Client {} disconnected: {}", client_id, reason);
             usernames.remove_entry(&client_id);
             if let Some(payload) = payload {
                 socket.send_to(payload, addr).unwrap();
@@ -1,8 +1,11 @@
-use std::{fmt, net::SocketAddr, time::Duration};
+use std::{fmt, net::SocketAddr, sync::Arc, time::Duration};
 
 use crate::{
-    packet::Packet, replay_protection::ReplayProtection, token::ConnectToken, ClientID, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES,
-    NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_SEND_RATE,
+    observer::{LogObserver, NetcodeObserver},
+    packet::Packet,
+    replay_protection::ReplayProtection,
+    token::ConnectToken,
+    ClientID, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_SEND_RATE,
 };
 
 /// The reason why a client is in error state
@@ -47,7 +50,9 @@ pub struct NetcodeClient {
     client_index: u32,
     send_rate: Duration,
     replay_protection: ReplayProtection,
+    paused: bool,
     out: [u8; NETCODE_MAX_PACKET_BYTES],
+    observer: Arc<dyn NetcodeObserver>,
 }
 
 impl fmt::Display for DisconnectReason {
@@ -87,7 +92,9 @@ impl NetcodeClient {
             challenge_token_data: [0u8; NETCODE_CHALLENGE_TOKEN_BYTES],
             connect_token,
             replay_protection: ReplayProtection::new(),
+            paused: false,
             out: [0u8; NETCODE_MAX_PACKET_BYTES],
+            observer: Arc::new(LogObserver),
         }
     }
 
@@ -106,10 +113,60 @@ impl NetcodeClient {
         self.current_time
     }
 
+    /// Sets the observer notified of the discarded packets, replacing the default [LogObserver].
+    pub fn set_observer<O: NetcodeObserver + 'static>(&mut self, observer: O) {
+        self.observer = Arc::new(observer);
+    }
+
+    /// Pauses the client, the time advanced while paused is ignored so the connection
+    /// does not time out and no packets are generated until the client is resumed.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes the client from where it was paused.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the time left before the connection times out if no packets are received
+    /// from the server, zero when it already timed out.
+    /// Returns [Duration::MAX] when the timeout is disabled in the connect token.
+    pub fn time_until_timeout(&self) -> Duration {
+        if self.connect_token.timeout_seconds <= 0 {
+            return Duration::MAX;
+        }
+
+        let timeout_time = self.last_packet_received_time + Duration::from_secs(self.connect_token.timeout_seconds as u64);
+        timeout_time.saturating_sub(self.current_time)
+    }
+
     pub fn client_id(&self) -> ClientID {
         self.client_id
     }
 
+    /// Returns the index of the client slot in the server, sent by the server when the connection is established.
+    /// Returns None if the client is not connected.
+    pub fn client_index(&self) -> Option<u32> {
+        if !self.is_connected() {
+            return None;
+        }
+        Some(self.client_index)
+    }
+
+    /// Returns the maximum number of clients of the server, sent by the server when the connection is established.
+    /// Returns None if the client is not connected.
+    pub fn max_clients(&self) -> Option<u32> {
+        if !self.is_connected() {
+            return None;
+        }
+        Some(self.max_clients)
+    }
+
     /// Returns the reason that the client was disconnected for.
     pub fn disconnected(&self) -> Option<DisconnectReason> {
         if let ClientState::Disconnected(reason) = &self.state {
@@ -148,8 +205,13 @@ impl NetcodeClient {
             Some(&mut self.replay_protection),
         ) {
             Ok((_, packet)) => packet,
+            Err(NetcodeError::DecryptionFailed) => {
+                // Not a bug, the packet was tampered or encrypted with another key
+                self.observer.on_decrypt_failure(self.server_addr);
+                return None;
+            }
             Err(e) => {
-                log::error!("Failed to decode packet: {}", e);
+                self.observer.on_invalid_packet(self.server_addr, &e);
                 return None;
             }
         };
@@ -196,6 +258,17 @@ impl NetcodeClient {
         None
     }
 
+    /// Same as [process_packet][Self::process_packet], but discards the packet if it was not
+    /// received from the server address.
+    pub fn process_packet_from<'a>(&mut self, buffer: &'a mut [u8], addr: SocketAddr) -> Option<&'a [u8]> {
+        if addr != self.server_addr {
+            log::debug!("Discarded packet from unknown address {}", addr);
+            return None;
+        }
+
+        self.process_packet(buffer)
+    }
+
     /// Returns the server address and an encrypted payload packet that can be sent to the server.
     pub fn generate_payload_packet(&mut self, payload: &[u8]) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
         if payload.len() > NETCODE_MAX_PAYLOAD_BYTES {
@@ -221,6 +294,10 @@ impl NetcodeClient {
     /// Update the internal state of the client, receives the duration since last updated.
     /// Might return the serve address and a protocol packet to be sent to the server.
     pub fn update(&mut self, duration: Duration) -> Option<(&mut [u8], SocketAddr)> {
+        if self.paused {
+            return None;
+        }
+
         if let Err(e) = self.update_internal_state(duration) {
             log::error!("Failed to update client: {}", e);
             return None;
@@ -326,7 +403,7 @@ impl NetcodeClient {
 
 #[cfg(test)]
 mod tests {
-    use crate::{crypto::generate_random_bytes, NETCODE_MAX_PACKET_BYTES};
+    use crate::{crypto::generate_random_bytes, NETCODE_MAX_PACKET_BYTES, NETCODE_TIMEOUT_SECONDS};
 
     use super::*;
 
@@ -372,6 +449,8 @@ mod tests {
         let (_, packet) = Packet::decode(packet_buffer, protocol_id, Some(&client_key), None).unwrap();
         assert!(matches!(packet, Packet::Response { .. }));
 
+        assert_eq!(client.client_index(), None);
+
         let max_clients = 4;
         let client_index = 2;
         let keep_alive_packet = Packet::KeepAlive { max_clients, client_index };
@@ -379,6 +458,11 @@ mod tests {
         client.process_packet(&mut buffer[..len]);
 
         assert_eq!(client.state, ClientState::Connected);
+        assert_eq!(client.client_index(), Some(client_index));
+        assert_eq!(client.max_clients(), Some(max_clients));
+
+        client.update(Duration::from_secs(2));
+        assert_eq!(client.time_until_timeout(), Duration::from_secs(NETCODE_TIMEOUT_SECONDS as u64 - 2));
 
         let payload = vec![7u8; 500];
         let payload_packet = Packet::Payload(&payload[..]);
@@ -387,6 +471,14 @@ mod tests {
         let payload_client = client.process_packet(&mut buffer[..len]).unwrap();
         assert_eq!(payload, payload_client);
 
+        let len = payload_packet.encode(&mut buffer, protocol_id, Some((3, &server_key))).unwrap();
+        let unknown_addr = "127.0.0.3:3000".parse().unwrap();
+        assert!(client.process_packet_from(&mut buffer[..len], unknown_addr).is_none());
+
+        let server_addr = client.server_addr();
+        let payload_client = client.process_packet_from(&mut buffer[..len], server_addr).unwrap();
+        assert_eq!(payload, payload_client);
+
         let to_send_payload = vec![5u8; 1000];
         let (_, packet) = client.generate_payload_packet(&to_send_payload).unwrap();
         let (_, result) = Packet::decode(packet, protocol_id, Some(&client_key), None).unwrap();
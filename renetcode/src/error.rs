@@ -28,6 +28,8 @@ pub enum NetcodeError {
     Disconnected(DisconnectReason),
     /// An error ocurred while encrypting or decrypting.
     CryptoError,
+    /// Failed to decrypt a received packet, it was tampered or encrypted with another key.
+    DecryptionFailed,
     /// The server address is not in the connect token.
     NotInHostList,
     /// Client was not found.
@@ -56,6 +58,7 @@ impl fmt::Display for NetcodeError {
             Disconnected(reason) => write!(fmt, "disconnected: {}", reason),
             NoMoreServers => write!(fmt, "client has no more servers to connect"),
             CryptoError => write!(fmt, "error while encoding or decoding"),
+            DecryptionFailed => write!(fmt, "failed to decrypt packet"),
             NotInHostList => write!(fmt, "token does not contain the server address"),
             ClientNotFound => write!(fmt, "client was not found"),
             ClientNotConnected => write!(fmt, "client is disconnected or connecting"),
@@ -19,6 +19,7 @@
 mod client;
 mod crypto;
 mod error;
+mod observer;
 mod packet;
 mod replay_protection;
 mod serialize;
@@ -28,6 +29,7 @@ mod token;
 pub use client::{DisconnectReason, NetcodeClient};
 pub use crypto::generate_random_bytes;
 pub use error::NetcodeError;
+pub use observer::{LogObserver, NetcodeObserver};
 pub use server::{NetcodeServer, ServerResult};
 pub use token::{ConnectToken, TokenGenerationError};
 
@@ -62,3 +64,10 @@ const NETCODE_ADDITIONAL_DATA_SIZE: usize = 13 + 8 + 8;
 const NETCODE_TIMEOUT_SECONDS: i32 = 15;
 
 const NETCODE_SEND_RATE: Duration = Duration::from_millis(250);
+
+// Only clients silent for this long are tried against packets from unknown addresses
+const NETCODE_REBIND_SILENCE: Duration = Duration::from_secs(1);
+// Minimum time between the rebinding attempts from the same address
+const NETCODE_REBIND_ATTEMPT_INTERVAL: Duration = NETCODE_SEND_RATE;
+// Maximum number of addresses whose last rebinding attempt is tracked
+const NETCODE_MAX_REBIND_ADDRESSES: usize = NETCODE_MAX_CLIENTS;
@@ -0,0 +1,26 @@
+use crate::NetcodeError;
+
+use std::fmt::Debug;
+use std::net::SocketAddr;
+
+/// Hooks for the packets discarded by a [NetcodeServer](crate::NetcodeServer) or a
+/// [NetcodeClient](crate::NetcodeClient), so diagnostics can be routed to a custom system
+/// instead of the log crate. The default implementations log the events, like the [LogObserver].
+/// The hooks take `&self`, use interior mutability to keep state in the observer.
+pub trait NetcodeObserver: Debug + Send + Sync {
+    /// Called when a packet could not be decrypted, it was tampered or encrypted with another key.
+    fn on_decrypt_failure(&self, addr: SocketAddr) {
+        log::warn!("Failed to decrypt packet from {}", addr);
+    }
+
+    /// Called when a packet is discarded because it could not be processed.
+    fn on_invalid_packet(&self, addr: SocketAddr, error: &NetcodeError) {
+        log::error!("Failed to process packet from {}: {}", addr, error);
+    }
+}
+
+/// Default observer, logs the events with the log crate.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogObserver;
+
+impl NetcodeObserver for LogObserver {}
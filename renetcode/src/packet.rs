@@ -71,6 +71,12 @@ impl PacketType {
         Ok(packet_type)
     }
 
+    /// Returns the packet type from the prefix byte of an encoded packet.
+    pub(crate) fn from_prefix(prefix_byte: u8) -> Result<Self, NetcodeError> {
+        let (packet_type, _) = decode_prefix(prefix_byte);
+        Self::from_u8(packet_type)
+    }
+
     fn apply_replay_protection(&self) -> bool {
         use PacketType::*;
 
@@ -283,7 +289,7 @@ impl<'a> Packet<'a> {
                 }
             }
 
-            dencrypted_in_place(&mut buffer[read_pos..], sequence, private_key, &aad)?;
+            dencrypted_in_place(&mut buffer[read_pos..], sequence, private_key, &aad).map_err(|_| NetcodeError::DecryptionFailed)?;
 
             if let Some(replay_protection) = replay_protection {
                 if packet_type.apply_replay_protection() {
@@ -328,7 +334,7 @@ impl ChallengeToken {
     ) -> Result<ChallengeToken, NetcodeError> {
         let mut decoded = [0u8; NETCODE_CHALLENGE_TOKEN_BYTES];
         decoded.copy_from_slice(&token_data);
-        dencrypted_in_place(&mut decoded, token_sequence, challenge_key, b"")?;
+        dencrypted_in_place(&mut decoded, token_sequence, challenge_key, b"").map_err(|_| NetcodeError::DecryptionFailed)?;
 
         Ok(ChallengeToken::read(&mut Cursor::new(&mut decoded))?)
     }
@@ -457,6 +463,19 @@ mod tests {
         assert_eq!(packet, d_packet);
     }
 
+    #[test]
+    fn decrypt_tampered_packet() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let key = b"an example very very secret key."; // 32-bytes
+        let packet = Packet::Disconnect;
+        let protocol_id = 12;
+        let len = packet.encode(&mut buffer, protocol_id, Some((1, key))).unwrap();
+        buffer[len - 1] ^= 1;
+
+        let result = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None);
+        assert!(matches!(result, Err(NetcodeError::DecryptionFailed)));
+    }
+
     #[test]
     fn encrypt_decrypt_denied_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
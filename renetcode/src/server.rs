@@ -1,13 +1,15 @@
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 use crate::{
     crypto::generate_random_bytes,
-    packet::{ChallengeToken, Packet},
+    observer::{LogObserver, NetcodeObserver},
+    packet::{ChallengeToken, Packet, PacketType},
     replay_protection::ReplayProtection,
     token::PrivateConnectToken,
-    ClientID, NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
-    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
-    NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
+    ClientID, DisconnectReason, NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES,
+    NETCODE_MAC_BYTES, NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS,
+    NETCODE_MAX_REBIND_ADDRESSES, NETCODE_REBIND_ATTEMPT_INTERVAL, NETCODE_REBIND_SILENCE, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
+    NETCODE_VERSION_INFO,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -56,8 +58,15 @@ pub struct NetcodeServer {
     challenge_key: [u8; NETCODE_KEY_BYTES],
     public_address: SocketAddr,
     current_time: Duration,
+    // Time the server was paused, the clients don't time out while paused
+    paused_time: Option<Duration>,
+    // Client that changed its address in the last processed packet, with its previous address
+    rebinded_client: Option<(ClientID, SocketAddr)>,
+    // Time of the last rebinding attempt from each unknown address
+    rebind_attempts: HashMap<SocketAddr, Duration>,
     global_sequence: u64,
     out: [u8; NETCODE_MAX_PACKET_BYTES],
+    observer: Arc<dyn NetcodeObserver>,
 }
 
 /// Result from processing an packet in the server
@@ -80,6 +89,7 @@ pub enum ServerResult<'a, 's> {
     ClientDisconnected {
         client_id: ClientID,
         addr: SocketAddr,
+        reason: DisconnectReason,
         payload: Option<&'s mut [u8]>,
     },
 }
@@ -122,7 +132,11 @@ impl NetcodeServer {
             challenge_key,
             public_address,
             current_time,
+            paused_time: None,
+            rebinded_client: None,
+            rebind_attempts: HashMap::with_capacity(NETCODE_MAX_REBIND_ADDRESSES),
             out: [0u8; NETCODE_MAX_PACKET_BYTES],
+            observer: Arc::new(LogObserver),
         }
     }
 
@@ -135,6 +149,38 @@ impl NetcodeServer {
         self.public_address
     }
 
+    /// Sets the observer notified of the discarded packets, replacing the default [LogObserver].
+    pub fn set_observer<O: NetcodeObserver + 'static>(&mut self, observer: O) {
+        self.observer = Arc::new(observer);
+    }
+
+    /// Pauses the timeout of the connected clients, the time passed while paused
+    /// is not counted towards their timeout. Packets are still processed while paused.
+    pub fn pause(&mut self) {
+        if self.paused_time.is_none() {
+            self.paused_time = Some(self.current_time);
+        }
+    }
+
+    /// Resumes the timeout of the connected clients from where it was paused.
+    pub fn resume(&mut self) {
+        if let Some(paused_time) = self.paused_time.take() {
+            let paused_duration = self.current_time - paused_time;
+            for client in self.clients.iter_mut().flatten() {
+                client.last_packet_received_time = (client.last_packet_received_time + paused_duration).min(self.current_time);
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_time.is_some()
+    }
+
+    // Current time for the client timeouts, it does not advance while paused
+    fn timeout_time(&self) -> Duration {
+        self.paused_time.unwrap_or(self.current_time)
+    }
+
     pub fn current_time(&self) -> Duration {
         self.current_time
     }
@@ -191,6 +237,18 @@ impl NetcodeServer {
         None
     }
 
+    /// Returns the time left before the client times out if no packets are received from it,
+    /// zero when it already timed out. Returns [Duration::MAX] when the timeout is disabled.
+    pub fn time_until_timeout(&self, client_id: ClientID) -> Option<Duration> {
+        let client = find_client_by_id(&self.clients, client_id)?;
+        if client.timeout_seconds <= 0 {
+            return Some(Duration::MAX);
+        }
+
+        let timeout_time = client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64);
+        Some(timeout_time.saturating_sub(self.timeout_time()))
+    }
+
     fn handle_connection_request<'a>(
         &mut self,
         addr: SocketAddr,
@@ -332,9 +390,16 @@ impl NetcodeServer {
     /// Process an packet from the especifed address. Returns a server result, check out
     /// [ServerResult].
     pub fn process_packet<'a, 's>(&'s mut self, addr: SocketAddr, buffer: &'a mut [u8]) -> ServerResult<'a, 's> {
+        self.rebinded_client = None;
+        let observer = self.observer.clone();
         match self.process_packet_internal(addr, buffer) {
+            Err(NetcodeError::DecryptionFailed) => {
+                // Not a bug, the packet was tampered or encrypted with another key
+                observer.on_decrypt_failure(addr);
+                ServerResult::None
+            }
             Err(e) => {
-                log::error!("Failed to process packet: {}", e);
+                observer.on_invalid_packet(addr, &e);
                 ServerResult::None
             }
             Ok(r) => r,
@@ -371,6 +436,7 @@ impl NetcodeServer {
                         return Ok(ServerResult::ClientDisconnected {
                             client_id,
                             addr,
+                            reason: DisconnectReason::DisconnectedByClient,
                             payload: None,
                         });
                     }
@@ -471,6 +537,16 @@ impl NetcodeServer {
             }
         }
 
+        // Handle connected client that changed its address (NAT rebinding),
+        // the address is only updated when the packet is decrypted with the client key
+        if let Some(slot) = self.find_client_slot_by_packet(addr, buffer) {
+            let client = self.clients[slot].as_mut().expect("client always exists in the slot");
+            log::debug!("Client {} changed address from {} to {}", client.client_id, client.addr, addr);
+            self.rebinded_client = Some((client.client_id, client.addr));
+            client.addr = addr;
+            return self.process_packet_internal(addr, buffer);
+        }
+
         // Handle new client
         let (_, packet) = Packet::decode(buffer, self.protocol_id, None, None)?;
         match packet {
@@ -485,6 +561,60 @@ impl NetcodeServer {
         }
     }
 
+    /// Returns the id and the previous address of the client that changed
+    /// its address in the last processed packet.
+    pub fn rebinded_client(&self) -> Option<(ClientID, SocketAddr)> {
+        self.rebinded_client
+    }
+
+    /// Returns the slot of the connected client whose key decrypts the packet.
+    /// Only the clients that stopped sending packets from their address are tried,
+    /// and each address can only make an attempt every send rate interval,
+    /// so forged packets can't make the server decrypt them with the key of every client.
+    fn find_client_slot_by_packet(&mut self, addr: SocketAddr, buffer: &[u8]) -> Option<usize> {
+        // Only connected clients send these packets, skip the decryption attempts for anything else
+        match PacketType::from_prefix(buffer[0]) {
+            Ok(PacketType::KeepAlive | PacketType::Payload | PacketType::Disconnect) => {}
+            _ => return None,
+        }
+        if buffer.len() > self.out.len() || !self.allow_rebind_attempt(addr) {
+            return None;
+        }
+
+        // The packet is decrypted in place, so each attempt works on a copy in the out buffer
+        let protocol_id = self.protocol_id;
+        let current_time = self.current_time;
+        let scratch = &mut self.out[..buffer.len()];
+        self.clients.iter().enumerate().find_map(|(slot, client)| match client {
+            Some(client)
+                if client.state == ConnectionState::Connected
+                    && client.last_packet_received_time + NETCODE_REBIND_SILENCE <= current_time =>
+            {
+                scratch.copy_from_slice(buffer);
+                let mut replay_protection = client.replay_protection.clone();
+                match Packet::decode(scratch, protocol_id, Some(&client.receive_key), Some(&mut replay_protection)) {
+                    Ok((_, Packet::ConnectionRequest { .. })) | Err(_) => None,
+                    Ok(_) => Some(slot),
+                }
+            }
+            _ => None,
+        })
+    }
+
+    /// Records a rebinding attempt from the address, returns false if the address
+    /// made an attempt recently or too many addresses are being tracked.
+    fn allow_rebind_attempt(&mut self, addr: SocketAddr) -> bool {
+        match self.rebind_attempts.get(&addr) {
+            Some(&last_attempt) if self.current_time < last_attempt + NETCODE_REBIND_ATTEMPT_INTERVAL => return false,
+            Some(_) => {}
+            None if self.rebind_attempts.len() >= NETCODE_MAX_REBIND_ADDRESSES => return false,
+            None => {}
+        }
+
+        self.rebind_attempts.insert(addr, self.current_time);
+        true
+    }
+
     pub fn clients_slot(&self) -> Vec<usize> {
         self.clients
             .iter()
@@ -523,6 +653,10 @@ impl NetcodeServer {
         }
 
         self.pending_clients.retain(|_, c| c.state != ConnectionState::Disconnected);
+
+        let current_time = self.current_time;
+        self.rebind_attempts
+            .retain(|_, last_attempt| current_time < *last_attempt + NETCODE_REBIND_ATTEMPT_INTERVAL);
     }
 
     /// Updates the client, returns a ServerResult.
@@ -545,9 +679,10 @@ impl NetcodeServer {
             Some(slot) => slot,
         };
 
+        let timeout_time = self.timeout_time();
         if let Some(client) = &mut self.clients[slot] {
             let connection_timed_out = client.timeout_seconds > 0
-                && (client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64) < self.current_time);
+                && (client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64) < timeout_time);
             if connection_timed_out {
                 log::debug!("Client {} disconnected, connection timed out", client.client_id);
                 client.state = ConnectionState::Disconnected;
@@ -566,6 +701,7 @@ impl NetcodeServer {
                         return ServerResult::ClientDisconnected {
                             client_id,
                             addr,
+                            reason: DisconnectReason::ConnectionTimedOut,
                             payload: None,
                         };
                     }
@@ -575,6 +711,7 @@ impl NetcodeServer {
                 return ServerResult::ClientDisconnected {
                     client_id,
                     addr,
+                    reason: DisconnectReason::ConnectionTimedOut,
                     payload: Some(&mut self.out[..len]),
                 };
             }
@@ -623,6 +760,7 @@ impl NetcodeServer {
                     return ServerResult::ClientDisconnected {
                         client_id,
                         addr: client.addr,
+                        reason: DisconnectReason::DisconnectedByServer,
                         payload: None,
                     };
                 }
@@ -631,6 +769,7 @@ impl NetcodeServer {
             return ServerResult::ClientDisconnected {
                 client_id,
                 addr: client.addr,
+                reason: DisconnectReason::DisconnectedByServer,
                 payload: Some(&mut self.out[..len]),
             };
         }
@@ -676,6 +815,73 @@ mod tests {
         NetcodeServer::new(Duration::ZERO, max_clients, TEST_PROTOCOL_ID, server_addr, *TEST_KEY)
     }
 
+    fn connect_client(server: &mut NetcodeServer, client_id: ClientID, client_addr: SocketAddr) -> NetcodeClient {
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID,
+            3,
+            client_id,
+            5,
+            vec![server.address()],
+            None,
+            TEST_KEY,
+        )
+        .unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+
+        while !client.is_connected() {
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                    client.process_packet(payload);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        client
+    }
+
+    #[test]
+    fn client_address_rebinding() {
+        let mut server = new_server();
+        let client_id = 4;
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let new_client_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let mut client = connect_client(&mut server, client_id, client_addr);
+        let client_payload = [2u8; 300];
+
+        // Clients still sending packets from their address are not tried
+        let (_, packet) = client.generate_payload_packet(&client_payload).unwrap();
+        assert_eq!(server.process_packet(new_client_addr, packet), ServerResult::None);
+        assert_eq!(server.client_addr(client_id), Some(client_addr));
+
+        // Packets that cannot be decrypted with the client key do not change its address
+        server.update(NETCODE_REBIND_SILENCE);
+        let mut invalid_packet = [0u8; 100];
+        invalid_packet[0] = PacketType::Payload as u8;
+        assert_eq!(server.process_packet(new_client_addr, &mut invalid_packet), ServerResult::None);
+        assert_eq!(server.client_addr(client_id), Some(client_addr));
+        assert_eq!(server.rebinded_client(), None);
+
+        // The address has to wait before trying again
+        let (_, packet) = client.generate_payload_packet(&client_payload).unwrap();
+        assert_eq!(server.process_packet(new_client_addr, packet), ServerResult::None);
+        assert_eq!(server.client_addr(client_id), Some(client_addr));
+
+        server.update(NETCODE_REBIND_ATTEMPT_INTERVAL);
+        let (_, packet) = client.generate_payload_packet(&client_payload).unwrap();
+        match server.process_packet(new_client_addr, packet) {
+            ServerResult::Payload { client_id: id, payload } => {
+                assert_eq!(id, client_id);
+                assert_eq!(client_payload, payload);
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(server.client_addr(client_id), Some(new_client_addr));
+        assert_eq!(server.rebinded_client(), Some((client_id, client_addr)));
+    }
+
     #[test]
     fn server_connection() {
         let mut server = new_server();
@@ -760,7 +966,9 @@ mod tests {
         let result = server.disconnect(client_id);
         match result {
             ServerResult::ClientDisconnected {
-                payload: Some(payload), ..
+                reason: DisconnectReason::DisconnectedByServer,
+                payload: Some(payload),
+                ..
             } => {
                 assert!(client.is_connected());
                 assert!(client.process_packet(payload).is_none());
@@ -772,6 +980,29 @@ mod tests {
         assert!(!server.is_client_connected(client_id));
     }
 
+    #[test]
+    fn protocol_id_mismatch() {
+        let mut server = new_server();
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID + 1,
+            3,
+            4,
+            5,
+            vec![server.address()],
+            None,
+            TEST_KEY,
+        )
+        .unwrap();
+        let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+
+        let result = server.process_packet_internal(client_addr, client_packet);
+        assert!(matches!(result, Err(NetcodeError::InvalidProtocolID)));
+        assert_eq!(server.connected_clients(), 0);
+    }
+
     #[test]
     fn connect_token_already_used() {
         let mut server = new_server();
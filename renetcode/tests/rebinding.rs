@@ -0,0 +1,85 @@
+//! Runs in its own test binary, the global allocator counts the allocations of every test in it.
+use renetcode::{ConnectToken, NetcodeClient, NetcodeServer, ServerResult, NETCODE_KEY_BYTES};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const TEST_KEY: &[u8; NETCODE_KEY_BYTES] = b"an example very very secret key."; // 32-bytes
+const TEST_PROTOCOL_ID: u64 = 7;
+
+// Counts the allocations made by each thread, so tests running in parallel don't interfere
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn connect_client(server: &mut NetcodeServer, client_id: u64, client_addr: SocketAddr) {
+    let connect_token = ConnectToken::generate(
+        Duration::ZERO,
+        TEST_PROTOCOL_ID,
+        3,
+        client_id,
+        15,
+        vec![server.address()],
+        None,
+        TEST_KEY,
+    )
+    .unwrap();
+    let mut client = NetcodeClient::new(Duration::ZERO, connect_token);
+
+    while !client.is_connected() {
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(client_addr, client_packet) {
+            ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                client.process_packet(payload);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn rebinding_flood_does_not_allocate() {
+    let server_addr = "127.0.0.1:5000".parse().unwrap();
+    let mut server = NetcodeServer::new(Duration::ZERO, 16, TEST_PROTOCOL_ID, server_addr, *TEST_KEY);
+    for client_id in 0..8 {
+        let client_addr = SocketAddr::from(([127, 0, 0, 1], 3000 + client_id as u16));
+        connect_client(&mut server, client_id, client_addr);
+    }
+
+    // The clients are silent, so the forged packets are tried against all of them
+    server.update(Duration::from_secs(2));
+
+    // Packets of every type from many unknown addresses
+    let mut packets = vec![[0u8; 100]; 7];
+    for (packet_type, packet) in packets.iter_mut().enumerate() {
+        packet[0] = packet_type as u8;
+    }
+
+    let allocations = ALLOCATIONS.with(|allocations| allocations.get());
+    for port in 0..100 {
+        let unknown_addr = SocketAddr::from(([127, 0, 0, 1], 4000 + port));
+        for packet in packets.iter_mut() {
+            assert_eq!(server.process_packet(unknown_addr, packet), ServerResult::None);
+        }
+    }
+    assert_eq!(ALLOCATIONS.with(|allocations| allocations.get()), allocations);
+    assert_eq!(server.connected_clients(), 8);
+}